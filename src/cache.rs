@@ -0,0 +1,344 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::UNIX_EPOCH;
+
+use crate::error::{Result, UsidError};
+use crate::hash::HashAlgorithm;
+use crate::manifest::{ManifestEntry, Normalization, ALGORITHM_VERSION};
+
+/// First line of a cache file. Caches written by another algorithm version are discarded.
+const HEADER: &str = "usid-cache";
+
+/// Identifies a version of a file on disk without reading it.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub(crate) struct FileStamp {
+    pub path: PathBuf,
+    pub size: u64,
+    /// Modification time in nanoseconds since the Unix epoch.
+    pub modified: u128,
+    pub inode: Option<u64>,
+}
+
+impl FileStamp {
+    /// Stamps the file at `path`, or returns `None` if its metadata is unavailable.
+    pub fn of(path: &Path) -> Option<Self> {
+        let metadata = fs::metadata(path).ok()?;
+        let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?.as_nanos();
+
+        #[cfg(unix)]
+        let inode = Some(std::os::unix::fs::MetadataExt::ino(&metadata));
+        #[cfg(not(unix))]
+        let inode = None;
+
+        Some(Self {
+            path: std::path::absolute(path).ok()?,
+            size: metadata.len(),
+            modified,
+            inode,
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+struct CachedDigest {
+    stamp: FileStamp,
+    algorithm: HashAlgorithm,
+    normalization: Normalization,
+    digest: String,
+    canonical_digest: String,
+    perceptual_hash: Option<String>,
+}
+
+/// Per-file digests from earlier USID computations, keyed by path, size,
+/// modification time and, where available, inode. Files whose stamp is unchanged
+/// are not read again.
+///
+/// Set [`HashOptions::cache`](crate::HashOptions::cache) to use a cache. It can be
+/// persisted with [`DigestCache::save`], which merges with and atomically replaces
+/// the file on disk while holding a lock on `<file>.lock`, so several processes
+/// can share one cache file.
+#[derive(Debug, Default)]
+pub struct DigestCache {
+    entries: Mutex<HashMap<PathBuf, CachedDigest>>,
+}
+
+impl DigestCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads a cache file. A missing file, or one written by another algorithm
+    /// version, yields an empty cache.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Self::new()),
+            Err(e) => return Err(UsidError::io(path, e)),
+        };
+
+        Ok(Self { entries: Mutex::new(parse(&contents)) })
+    }
+
+    /// Writes the cache to `path`, keeping entries another process saved there
+    /// in the meantime. The file is replaced atomically, and concurrent saves to
+    /// the same path are serialised through an advisory lock on `<path>.lock`.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+
+        let mut lock_path = path.as_os_str().to_owned();
+        lock_path.push(".lock");
+        let lock_path = PathBuf::from(lock_path);
+        let lock = fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&lock_path)
+            .map_err(|e| UsidError::io(&lock_path, e))?;
+        // Released when `lock` is dropped. Only other savers honour it; readers
+        // are safe anyway, as the file is replaced atomically.
+        lock.lock().map_err(|e| UsidError::io(&lock_path, e))?;
+
+        let mut merged = match fs::read_to_string(path) {
+            Ok(contents) => parse(&contents),
+            Err(_) => HashMap::new(),
+        };
+        merged.extend(self.lock().iter().map(|(k, v)| (k.clone(), v.clone())));
+
+        let mut contents = format!("{} {}\n", HEADER, ALGORITHM_VERSION);
+        let mut cached = merged.values().collect::<Vec<_>>();
+        cached.sort_by(|a, b| a.stamp.path.cmp(&b.stamp.path));
+        for entry in cached {
+            if let Some(line) = format_line(entry) {
+                contents.push_str(&line);
+                contents.push('\n');
+            }
+        }
+
+        let temp = path.with_extension(format!("tmp{}", std::process::id()));
+        let write = || -> io::Result<()> {
+            let mut file = fs::File::create(&temp)?;
+            file.write_all(contents.as_bytes())?;
+            file.sync_all()?;
+            fs::rename(&temp, path)
+        };
+
+        write().map_err(|e| {
+            let _ = fs::remove_file(&temp);
+            UsidError::io(path, e)
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
+
+    pub fn clear(&self) {
+        self.lock().clear();
+    }
+
+    /// Returns the cached entry for a file if its stamp, algorithm and
+    /// normalisation are unchanged.
+    pub(crate) fn get(
+        &self,
+        stamp: &FileStamp,
+        normalization: &Normalization,
+        algorithm: HashAlgorithm,
+        relative: &str,
+    ) -> Option<ManifestEntry> {
+        let entries = self.lock();
+        let cached = entries.get(&stamp.path)?;
+        if cached.stamp != *stamp || cached.algorithm != algorithm || cached.normalization != *normalization {
+            return None;
+        }
+
+        Some(ManifestEntry {
+            path: relative.to_string(),
+            size: cached.stamp.size,
+            digest: cached.digest.clone(),
+            canonical_digest: cached.canonical_digest.clone(),
+            normalization: cached.normalization.clone(),
+            perceptual_hash: cached.perceptual_hash.clone(),
+            role: None,
+        })
+    }
+
+    pub(crate) fn insert(&self, stamp: FileStamp, algorithm: HashAlgorithm, entry: &ManifestEntry) {
+        self.lock().insert(stamp.path.clone(), CachedDigest {
+            stamp,
+            algorithm,
+            normalization: entry.normalization.clone(),
+            digest: entry.digest.clone(),
+            canonical_digest: entry.canonical_digest.clone(),
+            perceptual_hash: entry.perceptual_hash.clone(),
+        });
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<PathBuf, CachedDigest>> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Parses a cache file, one tab-separated entry per line:
+/// path, size, modified, inode, algorithm, normalisation, digest, canonical
+/// digest, perceptual hash.
+fn parse(contents: &str) -> HashMap<PathBuf, CachedDigest> {
+    let mut lines = contents.lines();
+    if lines.next() != Some(&format!("{} {}", HEADER, ALGORITHM_VERSION)) {
+        return HashMap::new();
+    }
+
+    lines.filter_map(parse_line)
+        .map(|entry| (entry.stamp.path.clone(), entry))
+        .collect()
+}
+
+fn parse_line(line: &str) -> Option<CachedDigest> {
+    let mut fields = line.split('\t');
+    let path = PathBuf::from(fields.next()?);
+    let size = fields.next()?.parse().ok()?;
+    let modified = fields.next()?.parse().ok()?;
+    let inode = optional(fields.next()?).map(str::parse).transpose().ok()?;
+    let algorithm = fields.next()?.parse().ok()?;
+    let normalization = fields.next()?.parse().ok()?;
+    let digest = fields.next()?.to_string();
+    let canonical_digest = fields.next()?.to_string();
+    let perceptual_hash = optional(fields.next()?).map(String::from);
+
+    Some(CachedDigest {
+        stamp: FileStamp { path, size, modified, inode },
+        algorithm,
+        normalization,
+        digest,
+        canonical_digest,
+        perceptual_hash,
+    })
+}
+
+fn format_line(entry: &CachedDigest) -> Option<String> {
+    let path = entry.stamp.path.to_str()?;
+    // Paths that would break the line format are simply not persisted
+    if path.contains(['\t', '\n', '\r']) {
+        return None;
+    }
+
+    Some(format!(
+        "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+        path,
+        entry.stamp.size,
+        entry.stamp.modified,
+        entry.stamp.inode.map_or("-".to_string(), |i| i.to_string()),
+        entry.algorithm,
+        entry.normalization,
+        entry.digest,
+        entry.canonical_digest,
+        entry.perceptual_hash.as_deref().unwrap_or("-"),
+    ))
+}
+
+fn optional(field: &str) -> Option<&str> {
+    if field == "-" { None } else { Some(field) }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::{Duration, SystemTime};
+
+    use super::*;
+    use crate::{HashOptions, VoicebankFiles, USID};
+
+    fn bank(root: &Path, oto: &str) {
+        fs::write(root.join("character.txt"), "name=Teto\n").unwrap();
+        fs::write(root.join("oto.ini"), oto).unwrap();
+    }
+
+    fn usid(root: &Path, cache: &Arc<DigestCache>) -> USID {
+        let options = HashOptions { cache: Some(cache.clone()), ..Default::default() };
+        USID::from_files(&VoicebankFiles::from_dir(root).unwrap(), &options).unwrap()
+    }
+
+    #[test]
+    fn save_and_load_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("bank");
+        fs::create_dir(&root).unwrap();
+        bank(&root, "a.wav=a,0,0,0,0,0\n");
+
+        let cache = Arc::new(DigestCache::new());
+        let expected = usid(&root, &cache);
+        assert_eq!(cache.len(), 2);
+
+        let file = dir.path().join("cache.tsv");
+        cache.save(&file).unwrap();
+        let loaded = Arc::new(DigestCache::load(&file).unwrap());
+        assert_eq!(loaded.len(), 2);
+        assert_eq!(usid(&root, &loaded), expected);
+    }
+
+    #[test]
+    fn cache_of_another_algorithm_version_is_discarded() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("cache.tsv");
+        fs::write(&file, format!("{} 0\n/bank/oto.ini\t1\t1\t-\tblake3\toto\taa\tbb\t-\n", HEADER)).unwrap();
+        assert!(DigestCache::load(&file).unwrap().is_empty());
+    }
+
+    #[test]
+    fn changed_stamp_causes_a_rehash() {
+        let dir = tempfile::tempdir().unwrap();
+        bank(dir.path(), "a.wav=a,0,0,0,0,0\n");
+        let oto = dir.path().join("oto.ini");
+        let modified = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000);
+        fs::File::options().write(true).open(&oto).unwrap().set_modified(modified).unwrap();
+
+        let cache = Arc::new(DigestCache::new());
+        let before = usid(dir.path(), &cache);
+
+        // Same size and modification time: the stale digest is served from the cache
+        fs::write(&oto, "i.wav=i,0,0,0,0,0\n").unwrap();
+        fs::File::options().write(true).open(&oto).unwrap().set_modified(modified).unwrap();
+        assert_eq!(usid(dir.path(), &cache), before);
+
+        fs::File::options().write(true).open(&oto).unwrap().set_modified(modified + Duration::from_secs(1)).unwrap();
+        let after = usid(dir.path(), &cache);
+        assert_ne!(after, before);
+        assert_eq!(after, USID::from_voicebank(dir.path()).unwrap());
+    }
+
+    #[test]
+    fn concurrent_saves_keep_every_entry() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = Arc::new(dir.path().join("cache.tsv"));
+
+        let savers = (0..4)
+            .map(|i| {
+                let root = dir.path().join(format!("bank{}", i));
+                fs::create_dir(&root).unwrap();
+                bank(&root, &format!("{}.wav=a,0,0,0,0,0\n", i));
+
+                let cache = Arc::new(DigestCache::new());
+                usid(&root, &cache);
+                let file = file.clone();
+                thread::spawn(move || {
+                    for _ in 0..20 {
+                        cache.save(file.as_path()).unwrap();
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+
+        for saver in savers {
+            saver.join().unwrap();
+        }
+        assert_eq!(DigestCache::load(file.as_path()).unwrap().len(), 8);
+    }
+}
@@ -0,0 +1,225 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::library::{LibraryEntry, LibraryIndex};
+use crate::manifest::{ManifestEntry, Normalization, UsidManifest};
+use crate::USID;
+
+/// Voicebanks in a library that are copies of each other.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct DuplicateGroup {
+    pub kind: DuplicateKind,
+    /// The distinct USIDs of the group, sorted. Identical groups have exactly one.
+    pub usids: Vec<USID>,
+    /// The voicebanks in the group, sorted by path.
+    pub paths: Vec<PathBuf>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum DuplicateKind {
+    /// The voicebanks have the same USID.
+    Identical,
+    /// The voicebanks have different USIDs but the same format and identity
+    /// files apart from readmes and images, e.g. a re-release with a new portrait.
+    Similar,
+}
+
+impl UsidManifest {
+    /// Reports whether this manifest and `other` differ at most in their readme
+    /// and image files. Manifests made up of readmes and images alone are never
+    /// near-duplicates, as nothing of the voice itself is left to compare.
+    pub fn is_near_duplicate(&self, other: &UsidManifest) -> bool {
+        self.substantive_files().next().is_some()
+            && self.format == other.format
+            && self.algorithm == other.algorithm
+            && self.substantive_files().eq(other.substantive_files())
+    }
+
+    /// The identity files that define the voice itself, with their canonical digests.
+    fn substantive_files(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.files.iter()
+            .filter(|e| !is_presentational(e))
+            .map(|e| (e.path.as_str(), e.canonical_digest.as_str()))
+    }
+}
+
+impl LibraryIndex {
+    /// Groups the voicebanks of the library that share a USID, then those that
+    /// are near-duplicates of each other. A voicebank with identical copies is
+    /// reported in both a [`DuplicateKind::Identical`] group and, if it has
+    /// near-duplicates, a [`DuplicateKind::Similar`] one.
+    ///
+    /// Near-duplicates can only be found among entries that still have their
+    /// manifest, i.e. not in an index loaded from JSON.
+    pub fn duplicates(&self) -> Vec<DuplicateGroup> {
+        let mut groups = Vec::new();
+
+        let mut by_usid = BTreeMap::<USID, Vec<&LibraryEntry>>::new();
+        for entry in &self.entries {
+            if let Some(usid) = entry.usid {
+                by_usid.entry(usid).or_default().push(entry);
+            }
+        }
+
+        for (usid, entries) in &by_usid {
+            if entries.len() > 1 {
+                groups.push(group(DuplicateKind::Identical, vec![*usid], entries));
+            }
+        }
+
+        // Identical copies share a manifest, so one per USID is enough to group by
+        let mut by_substance = BTreeMap::<_, Vec<(&USID, &Vec<&LibraryEntry>)>>::new();
+        for (usid, entries) in &by_usid {
+            let Some(manifest) = entries.iter().find_map(|e| e.manifest.as_ref()) else {
+                continue;
+            };
+            if manifest.substantive_files().next().is_none() {
+                continue;
+            }
+
+            let key = (&manifest.format, manifest.algorithm.as_u8(), manifest.substantive_files().collect::<Vec<_>>());
+            by_substance.entry(key).or_default().push((usid, entries));
+        }
+
+        for similar in by_substance.values().filter(|s| s.len() > 1) {
+            let usids = similar.iter().map(|(usid, _)| **usid).collect();
+            let entries = similar.iter().flat_map(|(_, entries)| entries.iter().copied()).collect::<Vec<_>>();
+            groups.push(group(DuplicateKind::Similar, usids, &entries));
+        }
+
+        groups
+    }
+
+    /// Finds the voicebanks in the library that `manifest`, computed for the
+    /// voicebank at `path`, duplicates, e.g. to warn that a voicebank about to be
+    /// installed is already installed elsewhere. The voicebank at `path` itself
+    /// is never reported, even if it is part of the library.
+    pub fn duplicates_of(&self, path: impl AsRef<Path>, manifest: &UsidManifest) -> Vec<(&LibraryEntry, DuplicateKind)> {
+        let path = path.as_ref();
+        let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+
+        self.entries.iter()
+            .filter_map(|entry| {
+                if fs::canonicalize(&entry.path).unwrap_or_else(|_| entry.path.clone()) == path {
+                    None
+                } else if entry.usid == Some(manifest.usid) {
+                    Some((entry, DuplicateKind::Identical))
+                } else if entry.manifest.as_ref().is_some_and(|m| m.is_near_duplicate(manifest)) {
+                    Some((entry, DuplicateKind::Similar))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+}
+
+fn group(kind: DuplicateKind, usids: Vec<USID>, entries: &[&LibraryEntry]) -> DuplicateGroup {
+    let mut paths = entries.iter().map(|e| e.path.clone()).collect::<Vec<_>>();
+    paths.sort();
+    DuplicateGroup { kind, usids, paths }
+}
+
+/// Reports whether an identity file only describes how a voicebank is presented:
+/// its readme and its images.
+fn is_presentational(entry: &ManifestEntry) -> bool {
+    entry.normalization == Normalization::Image || entry.path.eq_ignore_ascii_case("readme.txt")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::HashAlgorithm;
+
+    fn entry(path: &str, canonical_digest: &str, normalization: Normalization) -> ManifestEntry {
+        ManifestEntry {
+            path: path.to_string(),
+            size: 0,
+            digest: canonical_digest.to_string(),
+            canonical_digest: canonical_digest.to_string(),
+            normalization,
+            perceptual_hash: None,
+            role: None,
+        }
+    }
+
+    fn manifest(usid: u8, files: Vec<ManifestEntry>) -> UsidManifest {
+        UsidManifest {
+            usid: USID::from_bytes(&[usid; 16]),
+            format: "utau".to_string(),
+            algorithm: HashAlgorithm::Blake3,
+            algorithm_version: 0,
+            files,
+            subbanks: Vec::new(),
+            family: None,
+        }
+    }
+
+    fn library(manifests: Vec<(&str, UsidManifest)>) -> LibraryIndex {
+        LibraryIndex {
+            entries: manifests.into_iter()
+                .map(|(path, manifest)| LibraryEntry {
+                    path: PathBuf::from(path),
+                    name: None,
+                    format: Some(manifest.format.clone()),
+                    usid: Some(manifest.usid),
+                    family: None,
+                    warnings: Vec::new(),
+                    manifest: Some(manifest),
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    fn oto(digest: &str) -> ManifestEntry {
+        entry("oto.ini", digest, Normalization::Oto)
+    }
+
+    fn readme(digest: &str) -> ManifestEntry {
+        entry("readme.txt", digest, Normalization::Text)
+    }
+
+    #[test]
+    fn banks_differing_in_readme_are_similar() {
+        let a = manifest(1, vec![oto("aa"), readme("r1")]);
+        let b = manifest(2, vec![oto("aa"), readme("r2")]);
+        assert!(a.is_near_duplicate(&b));
+
+        let groups = library(vec![("a", a), ("b", b)]).duplicates();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].kind, DuplicateKind::Similar);
+        assert_eq!(groups[0].paths, [PathBuf::from("a"), PathBuf::from("b")]);
+    }
+
+    #[test]
+    fn banks_differing_in_voice_are_not_similar() {
+        let a = manifest(1, vec![oto("aa")]);
+        let b = manifest(2, vec![oto("bb")]);
+        assert!(!a.is_near_duplicate(&b));
+        assert!(library(vec![("a", a), ("b", b)]).duplicates().is_empty());
+    }
+
+    #[test]
+    fn banks_of_only_readmes_and_images_are_not_similar() {
+        let a = manifest(1, vec![readme("r1")]);
+        let b = manifest(2, vec![readme("r2"), entry("icon.png", "i", Normalization::Image)]);
+        assert!(!a.is_near_duplicate(&b));
+        assert!(library(vec![("a", a), ("b", b)]).duplicates().is_empty());
+    }
+
+    #[test]
+    fn copies_are_identical() {
+        let groups = library(vec![("a", manifest(1, vec![oto("aa")])), ("b", manifest(1, vec![oto("aa")]))]).duplicates();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].kind, DuplicateKind::Identical);
+        assert_eq!(groups[0].usids, [USID::from_bytes(&[1; 16])]);
+    }
+}
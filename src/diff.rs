@@ -0,0 +1,253 @@
+use std::fmt::Display;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::hash::HashAlgorithm;
+use crate::manifest::UsidManifest;
+use crate::voicebank::NEAR_MATCH_DISTANCE;
+
+/// The differences between the identity files of two voicebanks. Files are
+/// paired by their [key](crate::ManifestEntry::key), so a referenced image that
+/// was renamed or converted is reported as changed rather than added and removed.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ManifestDiff {
+    /// Identity files only present in the second manifest.
+    pub added: Vec<String>,
+    /// Identity files only present in the first manifest.
+    pub removed: Vec<String>,
+    /// Identity files present in both manifests whose contents differ.
+    pub changed: Vec<FileChange>,
+    /// The hash algorithms of the two manifests, if they differ. Digests of
+    /// different algorithms cannot be compared, so `changed` is left empty.
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Option::is_none"))]
+    pub algorithms: Option<(HashAlgorithm, HashAlgorithm)>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct FileChange {
+    /// The path of the file, or its role if it has one.
+    pub path: String,
+    pub kind: ChangeKind,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum ChangeKind {
+    /// The file's bytes differ but normalise to the same contents, e.g. reordered
+    /// oto entries or different whitespace. Does not affect the USID.
+    Cosmetic,
+    /// An image whose pixels differ but that looks nearly the same, e.g. a portrait
+    /// re-saved as a lossy JPEG. Changes the USID.
+    Similar,
+    /// The file's normalised contents differ. Changes the USID.
+    Substantive,
+}
+
+impl ManifestDiff {
+    /// Reports whether the two voicebanks have byte-identical identity files.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty() && self.algorithms.is_none()
+    }
+
+    /// Reports whether every difference is cosmetic, i.e. both voicebanks have the same USID.
+    pub fn is_cosmetic(&self) -> bool {
+        self.algorithms.is_none()
+            && self.added.is_empty()
+            && self.removed.is_empty()
+            && self.changed.iter().all(|c| c.kind == ChangeKind::Cosmetic)
+    }
+}
+
+impl UsidManifest {
+    /// Compares the identity files of this manifest with those of `other`. If
+    /// the manifests were hashed with different algorithms, only added and
+    /// removed files are reported, along with the [algorithms](ManifestDiff::algorithms).
+    pub fn diff(&self, other: &UsidManifest) -> ManifestDiff {
+        let mut diff = ManifestDiff::default();
+        if self.algorithm != other.algorithm {
+            diff.algorithms = Some((self.algorithm, other.algorithm));
+        }
+
+        for entry in &self.files {
+            let Some(theirs) = other.entry_by_key(entry.key()) else {
+                diff.removed.push(entry.key().to_string());
+                continue;
+            };
+
+            if diff.algorithms.is_some() {
+                continue;
+            }
+
+            let kind = if entry.canonical_digest != theirs.canonical_digest {
+                if is_near_match(entry.perceptual_hash.as_deref(), theirs.perceptual_hash.as_deref()) {
+                    ChangeKind::Similar
+                } else {
+                    ChangeKind::Substantive
+                }
+            } else if entry.digest != theirs.digest {
+                ChangeKind::Cosmetic
+            } else {
+                continue;
+            };
+
+            diff.changed.push(FileChange { path: entry.key().to_string(), kind });
+        }
+
+        diff.added = other.files.iter()
+            .filter(|e| self.entry_by_key(e.key()).is_none())
+            .map(|e| e.key().to_string())
+            .collect();
+
+        diff
+    }
+}
+
+/// Reports whether two hex-encoded perceptual hashes are within near-match distance.
+fn is_near_match(a: Option<&str>, b: Option<&str>) -> bool {
+    let (Some(a), Some(b)) = (a, b) else {
+        return false;
+    };
+
+    match (u64::from_str_radix(a, 16), u64::from_str_radix(b, 16)) {
+        (Ok(a), Ok(b)) => (a ^ b).count_ones() <= NEAR_MATCH_DISTANCE,
+        _ => false,
+    }
+}
+
+impl Display for ManifestDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some((ours, theirs)) = self.algorithms {
+            writeln!(f, "! hashed with {} and {}, contents not compared", ours, theirs)?;
+        }
+        for path in &self.added {
+            writeln!(f, "+ {}", path)?;
+        }
+        for path in &self.removed {
+            writeln!(f, "- {}", path)?;
+        }
+        for change in &self.changed {
+            let kind = match change.kind {
+                ChangeKind::Cosmetic => "cosmetic",
+                ChangeKind::Similar => "similar",
+                ChangeKind::Substantive => "substantive",
+            };
+            writeln!(f, "~ {} ({})", change.path, kind)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manifest::{ManifestEntry, Normalization};
+    use crate::USID;
+
+    fn entry(path: &str, digest: &str, canonical_digest: &str) -> ManifestEntry {
+        ManifestEntry {
+            path: path.to_string(),
+            size: 0,
+            digest: digest.to_string(),
+            canonical_digest: canonical_digest.to_string(),
+            normalization: Normalization::Oto,
+            perceptual_hash: None,
+            role: None,
+        }
+    }
+
+    fn image(path: &str, canonical_digest: &str, perceptual_hash: &str) -> ManifestEntry {
+        ManifestEntry {
+            normalization: Normalization::Image,
+            perceptual_hash: Some(perceptual_hash.to_string()),
+            role: Some("image:portrait".to_string()),
+            ..entry(path, canonical_digest, canonical_digest)
+        }
+    }
+
+    fn manifest(files: Vec<ManifestEntry>) -> UsidManifest {
+        UsidManifest {
+            usid: USID::new(),
+            format: "utau".to_string(),
+            algorithm: HashAlgorithm::Blake3,
+            algorithm_version: 0,
+            files,
+            subbanks: Vec::new(),
+            family: None,
+        }
+    }
+
+    fn change(path: &str, kind: ChangeKind) -> FileChange {
+        FileChange { path: path.to_string(), kind }
+    }
+
+    #[test]
+    fn identical_manifests_have_no_differences() {
+        let a = manifest(vec![entry("oto.ini", "aa", "cc")]);
+        assert!(a.diff(&a).is_empty());
+    }
+
+    #[test]
+    fn same_canonical_contents_are_cosmetic() {
+        let a = manifest(vec![entry("oto.ini", "aa", "cc")]);
+        let b = manifest(vec![entry("oto.ini", "bb", "cc")]);
+        let diff = a.diff(&b);
+        assert_eq!(diff.changed, [change("oto.ini", ChangeKind::Cosmetic)]);
+        assert!(diff.is_cosmetic());
+        assert!(!diff.is_empty());
+    }
+
+    #[test]
+    fn different_canonical_contents_are_substantive() {
+        let a = manifest(vec![entry("oto.ini", "aa", "cc")]);
+        let b = manifest(vec![entry("oto.ini", "bb", "dd")]);
+        let diff = a.diff(&b);
+        assert_eq!(diff.changed, [change("oto.ini", ChangeKind::Substantive)]);
+        assert!(!diff.is_cosmetic());
+    }
+
+    #[test]
+    fn images_are_paired_by_role() {
+        let a = manifest(vec![image("icon.bmp", "aa", "00000000000000ff")]);
+
+        let converted = manifest(vec![image("icon.png", "aa", "00000000000000ff")]);
+        assert!(a.diff(&converted).is_empty());
+
+        let recompressed = manifest(vec![image("icon.jpg", "bb", "00000000000000fe")]);
+        assert_eq!(a.diff(&recompressed).changed, [change("image:portrait", ChangeKind::Similar)]);
+
+        let replaced = manifest(vec![image("new.png", "bb", "ffffffffffffff00")]);
+        assert_eq!(a.diff(&replaced).changed, [change("image:portrait", ChangeKind::Substantive)]);
+    }
+
+    #[test]
+    fn added_and_removed_files_are_listed() {
+        let a = manifest(vec![entry("oto.ini", "aa", "cc"), entry("A3/oto.ini", "aa", "cc")]);
+        let b = manifest(vec![entry("oto.ini", "aa", "cc"), entry("prefix.map", "aa", "cc")]);
+        let diff = a.diff(&b);
+        assert_eq!(diff.added, ["prefix.map"]);
+        assert_eq!(diff.removed, ["A3/oto.ini"]);
+        assert!(diff.changed.is_empty());
+        assert_eq!(diff.to_string(), "+ prefix.map\n- A3/oto.ini\n");
+    }
+
+    #[test]
+    fn different_algorithms_are_reported_without_comparing_contents() {
+        let a = manifest(vec![entry("oto.ini", "aa", "cc"), entry("A3/oto.ini", "aa", "cc")]);
+        let b = UsidManifest {
+            algorithm: HashAlgorithm::Sha256,
+            ..manifest(vec![entry("oto.ini", "bb", "dd")])
+        };
+
+        let diff = a.diff(&b);
+        assert_eq!(diff.algorithms, Some((HashAlgorithm::Blake3, HashAlgorithm::Sha256)));
+        assert_eq!(diff.removed, ["A3/oto.ini"]);
+        assert!(diff.changed.is_empty());
+        assert!(!diff.is_empty());
+        assert!(!diff.is_cosmetic());
+        assert!(diff.to_string().starts_with("! hashed with blake3 and sha256"));
+    }
+}
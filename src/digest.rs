@@ -0,0 +1,163 @@
+use std::fmt::Display;
+use std::path::Path;
+use std::str::FromStr;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize, Serializer};
+
+use crate::error::{Result, UsidError};
+use crate::hash::HashAlgorithm;
+use crate::manifest::UsidManifest;
+use crate::options::HashOptions;
+use crate::version::{self, Version};
+use crate::voicebank::{self, VoicebankFiles};
+use crate::USID;
+
+/// The full-length content hash a USID is truncated from, for tamper detection.
+///
+/// Its text form is the algorithm name followed by the hex-encoded digest, e.g.
+/// `blake3:af1349b9...`. [`UsidDigest::to_usid`] yields exactly the USID that
+/// [`USID::from_voicebank`] computes for the same voicebank and algorithm.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct UsidDigest {
+    algorithm: HashAlgorithm,
+    bytes: Vec<u8>,
+}
+
+impl UsidDigest {
+    /// Creates a digest from its raw bytes, which must be as long as the
+    /// algorithm's digests.
+    pub fn new(algorithm: HashAlgorithm, bytes: Vec<u8>) -> Result<Self> {
+        if bytes.len() != algorithm.digest_len() {
+            return Err(UsidError::InvalidLength { expected: algorithm.digest_len(), found: bytes.len() });
+        }
+
+        Ok(Self { algorithm, bytes })
+    }
+
+    /// Computes the digest of the voicebank at `path`.
+    pub fn from_voicebank(path: impl AsRef<Path>) -> Result<Self> {
+        Self::from_voicebank_with(path, &HashOptions::default())
+    }
+
+    /// Like [`UsidDigest::from_voicebank`], but with custom formats or settings.
+    pub fn from_voicebank_with(path: impl AsRef<Path>, options: &HashOptions) -> Result<Self> {
+        Self::from_files(&VoicebankFiles::from_dir(path)?, options)
+    }
+
+    /// Computes the digest of an already listed voicebank.
+    pub fn from_files(files: &VoicebankFiles, options: &HashOptions) -> Result<Self> {
+        Ok(UsidManifest::from_files(files, options)?.digest())
+    }
+
+    pub fn algorithm(&self) -> HashAlgorithm {
+        self.algorithm
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Truncates the digest to a USID, stamping the version and algorithm bits.
+    pub fn to_usid(&self) -> USID {
+        let mut data = [0; 16];
+        data.copy_from_slice(&self.bytes[..16]);
+        let data = version::stamp(data, Version::Content);
+        USID { data: version::stamp_algorithm(data, self.algorithm) }
+    }
+
+    /// Reports whether `usid` was truncated from this digest.
+    pub fn matches(&self, usid: &USID) -> bool {
+        self.to_usid() == *usid
+    }
+
+    /// Recomputes the digest of the voicebank at `path` with this digest's
+    /// algorithm, and fails with [`UsidError::DigestMismatch`] if it differs.
+    /// Hashes with the default [`HashOptions`], so digests computed with
+    /// [`HashOptions::fingerprint_audio`] must be checked with
+    /// [`UsidDigest::verify_files`] instead.
+    pub fn verify(&self, path: impl AsRef<Path>) -> Result<()> {
+        self.verify_files(&VoicebankFiles::from_dir(path)?, &HashOptions::default())
+    }
+
+    /// Like [`UsidDigest::verify`], for an already listed voicebank. The
+    /// algorithm set in `options` is ignored in favour of this digest's.
+    pub fn verify_files(&self, files: &VoicebankFiles, options: &HashOptions) -> Result<()> {
+        let found = voicebank::manifest(files, options, self.algorithm)?.digest();
+        if found != *self {
+            return Err(UsidError::DigestMismatch { expected: self.clone(), found });
+        }
+
+        Ok(())
+    }
+}
+
+impl From<&UsidDigest> for USID {
+    fn from(digest: &UsidDigest) -> Self {
+        digest.to_usid()
+    }
+}
+
+impl From<UsidDigest> for USID {
+    fn from(digest: UsidDigest) -> Self {
+        digest.to_usid()
+    }
+}
+
+impl Display for UsidDigest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:", self.algorithm)?;
+        for byte in &self.bytes {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for UsidDigest {
+    type Err = UsidError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let Some((name, hex)) = s.split_once(':') else {
+            return Err(UsidError::InvalidDigest(format!("missing algorithm in {:?}", s)));
+        };
+
+        let algorithm = name.parse::<HashAlgorithm>()?;
+
+        if let Some((i, c)) = hex.char_indices().find(|(_, c)| !c.is_ascii_hexdigit()) {
+            return Err(UsidError::InvalidCharacter { position: name.len() + 1 + i, character: c });
+        }
+
+        if hex.len() != algorithm.digest_len() * 2 {
+            return Err(UsidError::InvalidLength { expected: algorithm.digest_len(), found: hex.len() / 2 });
+        }
+
+        let bytes = (0..hex.len()).step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16))
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(|e| UsidError::InvalidDigest(e.to_string()))?;
+
+        Self::new(algorithm, bytes)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Serialize for UsidDigest {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for UsidDigest {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let digest: String = Deserialize::deserialize(deserializer)?;
+        digest.parse().map_err(serde::de::Error::custom)
+    }
+}
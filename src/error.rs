@@ -0,0 +1,95 @@
+use std::fmt::Display;
+use std::io;
+use std::path::PathBuf;
+
+use crate::{UsidDigest, Version, USID};
+
+pub type Result<T, E = UsidError> = std::result::Result<T, E>;
+
+/// Errors produced while parsing or computing a USID.
+#[derive(Debug)]
+pub enum UsidError {
+    /// The string does not start with the `usid:` prefix.
+    InvalidPrefix,
+    /// The input has the wrong length.
+    InvalidLength { expected: usize, found: usize },
+    /// An unexpected character was found at the given byte position.
+    InvalidCharacter { position: usize, character: char },
+    /// The USID was produced by a scheme this version of the crate does not know.
+    UnsupportedVersion(u8),
+    /// A content-derived USID names a hash algorithm this version of the crate does not know.
+    UnsupportedAlgorithm(u8),
+    /// Only content-derived USIDs can be verified against a voicebank.
+    NotContentDerived(Version),
+    /// A recomputed USID does not match the expected one.
+    ChecksumMismatch { expected: USID, found: USID },
+    /// A recomputed full-length digest does not match the expected one.
+    DigestMismatch { expected: UsidDigest, found: UsidDigest },
+    /// A hash algorithm name is not one of `blake3`, `sha256` or `xxh3`.
+    UnknownAlgorithm(String),
+    /// A normalisation name is not one this version of the crate knows.
+    UnknownNormalization(String),
+    /// A digest string is malformed.
+    InvalidDigest(String),
+    /// The voicebank path is not a directory.
+    NotADirectory(PathBuf),
+    /// The voicebank contains none of the files that define its identity.
+    NoIdentityFiles(PathBuf),
+    /// Reading a voicebank file failed.
+    Io { path: PathBuf, source: io::Error },
+    /// A voicebank archive is malformed or could not be read.
+    InvalidArchive(String),
+    /// The thread pool to hash files on could not be started.
+    ThreadPool(rayon::ThreadPoolBuildError),
+}
+
+impl UsidError {
+    pub(crate) fn io(path: impl Into<PathBuf>, source: io::Error) -> Self {
+        Self::Io { path: path.into(), source }
+    }
+}
+
+impl Display for UsidError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidPrefix => write!(f, "Invalid USID format: expected \"usid:\" prefix"),
+            Self::InvalidLength { expected, found } => {
+                write!(f, "Invalid USID length: expected {} bytes, found {}", expected, found)
+            }
+            Self::InvalidCharacter { position, character } => {
+                write!(f, "Invalid USID format: invalid character {:?} at position {}", character, position)
+            }
+            Self::UnsupportedVersion(version) => write!(f, "Unsupported USID version {}", version),
+            Self::UnsupportedAlgorithm(algorithm) => write!(f, "Unsupported USID hash algorithm {}", algorithm),
+            Self::NotContentDerived(version) => {
+                write!(f, "Cannot verify a {:?} USID, only content-derived USIDs can be verified", version)
+            }
+            Self::ChecksumMismatch { expected, found } => {
+                write!(f, "USID mismatch: expected {}, found {}", expected, found)
+            }
+            Self::DigestMismatch { expected, found } => {
+                write!(f, "Digest mismatch: expected {}, found {}", expected, found)
+            }
+            Self::UnknownAlgorithm(name) => {
+                write!(f, "Unknown hash algorithm {:?}, expected blake3, sha256 or xxh3", name)
+            }
+            Self::UnknownNormalization(name) => write!(f, "Unknown normalisation {:?}", name),
+            Self::InvalidDigest(message) => write!(f, "Invalid USID digest: {}", message),
+            Self::NotADirectory(path) => write!(f, "Voicebank path {} is not a directory", path.display()),
+            Self::NoIdentityFiles(path) => write!(f, "No identity files found in voicebank {}", path.display()),
+            Self::Io { path, source } => write!(f, "Failed to read {}: {}", path.display(), source),
+            Self::InvalidArchive(message) => write!(f, "Invalid voicebank archive: {}", message),
+            Self::ThreadPool(source) => write!(f, "Failed to start hashing threads: {}", source),
+        }
+    }
+}
+
+impl std::error::Error for UsidError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io { source, .. } => Some(source),
+            Self::ThreadPool(source) => Some(source),
+            _ => None,
+        }
+    }
+}
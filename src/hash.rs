@@ -0,0 +1,153 @@
+use std::fmt::Display;
+use std::io::{self, Read, Write};
+use std::str::FromStr;
+
+use sha2::Digest;
+use xxhash_rust::xxh3::Xxh3;
+
+use crate::error::{Result, UsidError};
+
+/// The hash function behind a content-derived USID. It is recorded in the USID,
+/// see [`USID::algorithm`](crate::USID::algorithm).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum HashAlgorithm {
+    #[default]
+    Blake3 = 0,
+    Sha256 = 1,
+    /// 128-bit xxHash3. Fast but not cryptographic, so only suitable for local
+    /// deduplication, never for tamper detection.
+    Xxh3 = 2,
+}
+
+impl HashAlgorithm {
+    pub fn from_u8(id: u8) -> Option<Self> {
+        match id {
+            0 => Some(Self::Blake3),
+            1 => Some(Self::Sha256),
+            2 => Some(Self::Xxh3),
+            _ => None,
+        }
+    }
+
+    pub fn as_u8(&self) -> u8 {
+        *self as u8
+    }
+
+    /// Length of a full digest in bytes.
+    pub fn digest_len(&self) -> usize {
+        match self {
+            Self::Blake3 | Self::Sha256 => 32,
+            Self::Xxh3 => 16,
+        }
+    }
+
+    /// Hashes `bytes` and returns the hex-encoded digest.
+    pub(crate) fn hex_digest(&self, bytes: &[u8]) -> String {
+        let mut hasher = Hasher::new(*self);
+        hasher.update(bytes);
+        hasher.finalize_hex()
+    }
+}
+
+impl Display for HashAlgorithm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Blake3 => write!(f, "blake3"),
+            Self::Sha256 => write!(f, "sha256"),
+            Self::Xxh3 => write!(f, "xxh3"),
+        }
+    }
+}
+
+impl FromStr for HashAlgorithm {
+    type Err = UsidError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "blake3" => Ok(Self::Blake3),
+            "sha256" => Ok(Self::Sha256),
+            "xxh3" => Ok(Self::Xxh3),
+            _ => Err(UsidError::UnknownAlgorithm(s.to_string())),
+        }
+    }
+}
+
+/// An incremental hasher for any [`HashAlgorithm`].
+pub(crate) enum Hasher {
+    Blake3(Box<blake3::Hasher>),
+    Sha256(sha2::Sha256),
+    Xxh3(Box<Xxh3>),
+}
+
+impl Hasher {
+    pub fn new(algorithm: HashAlgorithm) -> Self {
+        match algorithm {
+            HashAlgorithm::Blake3 => Self::Blake3(Box::default()),
+            HashAlgorithm::Sha256 => Self::Sha256(sha2::Sha256::new()),
+            HashAlgorithm::Xxh3 => Self::Xxh3(Box::default()),
+        }
+    }
+
+    pub fn update(&mut self, bytes: &[u8]) {
+        match self {
+            Self::Blake3(hasher) => {
+                hasher.update(bytes);
+            }
+            Self::Sha256(hasher) => hasher.update(bytes),
+            Self::Xxh3(hasher) => hasher.update(bytes),
+        }
+    }
+
+    pub fn finalize(self) -> Vec<u8> {
+        match self {
+            Self::Blake3(hasher) => hasher.finalize().as_bytes().to_vec(),
+            Self::Sha256(hasher) => hasher.finalize().to_vec(),
+            Self::Xxh3(hasher) => hasher.digest128().to_be_bytes().to_vec(),
+        }
+    }
+
+    pub fn finalize_hex(self) -> String {
+        self.finalize().iter().map(|b| format!("{:02x}", b)).collect()
+    }
+}
+
+impl Write for Hasher {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Hashes and counts the bytes read through it, so a file can be hashed while
+/// another consumer parses it.
+pub(crate) struct HashingReader<R> {
+    inner: R,
+    hasher: Hasher,
+    len: u64,
+}
+
+impl<R: Read> HashingReader<R> {
+    pub(crate) fn new(inner: R, algorithm: HashAlgorithm) -> Self {
+        Self { inner, hasher: Hasher::new(algorithm), len: 0 }
+    }
+
+    /// Returns the number of bytes read and their hex-encoded digest.
+    pub(crate) fn finish(self) -> (u64, String) {
+        (self.len, self.hasher.finalize_hex())
+    }
+}
+
+impl<R: Read> Read for HashingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.hasher.update(&buf[..n]);
+        self.len += n as u64;
+        Ok(n)
+    }
+}
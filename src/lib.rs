@@ -1,61 +1,227 @@
 use std::fmt::Display;
+use std::str::FromStr;
+use std::path::Path;
 use uuid::Uuid;
-use anyhow::{bail, Result};
+
+mod cache;
+mod dedup;
+mod diff;
+mod digest;
+mod error;
+mod hash;
+mod library;
+mod lineage;
+mod manifest;
+mod options;
+mod version;
+mod voicebank;
+
+pub use cache::DigestCache;
+pub use dedup::{DuplicateGroup, DuplicateKind};
+pub use diff::{ChangeKind, FileChange, ManifestDiff};
+pub use digest::UsidDigest;
+pub use error::{Result, UsidError};
+pub use hash::HashAlgorithm;
+pub use library::{LibraryEntry, LibraryIndex, ScanProgress};
+pub use manifest::{ManifestEntry, Normalization, UsidManifest, ALGORITHM_VERSION};
+pub use options::HashOptions;
+pub use version::Version;
+pub use voicebank::{
+    DiffSingerFormat, EnunuFormat, FormatRegistry, GenericFormat, UtauFormat, VoicebankFiles, VoicebankFormat,
+};
+
+/// Prefix of the canonical USID text form.
+const PREFIX: &str = "usid:";
+/// Number of hex digits between dashes in the text form.
+const GROUP_LEN: usize = 4;
+/// Length of the text form: the prefix, 32 hex digits and 7 dashes.
+const ENCODED_LEN: usize = PREFIX.len() + 32 + 32 / GROUP_LEN - 1;
 
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize, Serializer};
 
-#[derive(Default, Clone, Copy, Debug, Eq, Hash)]
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 /// flutter_rust_bridge:opaque
 pub struct USID {
     pub data: [u8; 16]
 }
 
 impl USID {
+    // The namespaces are permanent: every name-based USID in the ecosystem is
+    // derived from them. Each is the UUIDv5 of `usid:namespace:<name>` in the
+    // URL namespace, which the tests recompute.
+    /// Namespace for voicebanks not tied to a particular engine (OpenVB). The UUIDv5 of `usid:namespace:openvb` in [`Uuid::NAMESPACE_URL`].
+    pub const NAMESPACE_OPENVB: USID = USID { data: [0xa6, 0x04, 0x68, 0x7c, 0xc1, 0x74, 0x57, 0x74, 0xbd, 0x11, 0x04, 0xe7, 0xdd, 0xa2, 0xbe, 0xbc] };
+    /// Namespace for classic UTAU voicebanks. The UUIDv5 of `usid:namespace:utau` in [`Uuid::NAMESPACE_URL`].
+    pub const NAMESPACE_UTAU: USID = USID { data: [0x31, 0xb2, 0xf7, 0x85, 0x99, 0x0e, 0x56, 0x14, 0xa6, 0xf7, 0xed, 0x23, 0x3e, 0x23, 0x29, 0x83] };
+    /// Namespace for OpenUtau voicebanks. The UUIDv5 of `usid:namespace:openutau` in [`Uuid::NAMESPACE_URL`].
+    pub const NAMESPACE_OPENUTAU: USID = USID { data: [0x6f, 0x0e, 0x74, 0xd9, 0x2b, 0xff, 0x54, 0xb6, 0x8a, 0x43, 0xe6, 0x12, 0xcb, 0x79, 0x7a, 0x7c] };
+    /// Namespace for DiffSinger voicebanks. The UUIDv5 of `usid:namespace:diffsinger` in [`Uuid::NAMESPACE_URL`].
+    pub const NAMESPACE_DIFFSINGER: USID = USID { data: [0x98, 0xab, 0x25, 0x9e, 0x39, 0xdb, 0x55, 0x1b, 0x8f, 0xd5, 0xb3, 0x6b, 0x4e, 0x63, 0x31, 0x7b] };
+    /// Namespace for ENUNU/NNSVS voicebanks. The UUIDv5 of `usid:namespace:enunu` in [`Uuid::NAMESPACE_URL`].
+    pub const NAMESPACE_ENUNU: USID = USID { data: [0xa0, 0xf8, 0xde, 0x0e, 0x2d, 0x3f, 0x51, 0xc1, 0x9f, 0xd0, 0x82, 0x9e, 0x7b, 0x9a, 0x6f, 0x63] };
+
     pub fn new() -> Self {
         Self { data: [0; 16] }
     }
 
+    /// Generates a random USID, e.g. a provisional identity for a voicebank that is
+    /// still being authored. Its version is always [`Version::Random`].
+    pub fn random() -> Self {
+        Self::from_uuid(Uuid::new_v4())
+    }
+
+    /// Derives a deterministic USID from a namespace and a name, like a UUIDv5.
+    /// Its version is always [`Version::Name`].
+    ///
+    /// Names can be scoped further by deriving a namespace first, e.g. an author's
+    /// namespace from their handle and then the voicebank's USID from its name.
+    pub fn from_name(namespace: &USID, name: &str) -> Self {
+        Self::from_uuid(Uuid::new_v5(&namespace.as_uuid(), name.as_bytes()))
+    }
+
+    /// Derives the USID of voicebank `name` published by `author` within `namespace`.
+    pub fn from_author(namespace: &USID, author: &str, name: &str) -> Self {
+        Self::from_name(&Self::from_name(namespace, author), name)
+    }
+
+    /// Derives the USID of a subbank of this voicebank, such as a pitch or an
+    /// expression, e.g. `parent.child("A3_power")`. The result is a
+    /// [`Version::Name`] USID scoped to this one, so a project file can refer to
+    /// the subbank and still be resolved to its voicebank with
+    /// [`UsidManifest::subbank`]. Names are compared exactly, including case.
+    pub fn child(&self, name: &str) -> Self {
+        Self::from_name(self, name)
+    }
+
     pub fn from_uuid(uuid: Uuid) -> Self {
-        Self { data: uuid.into_bytes().clone() }
+        Self { data: uuid.into_bytes() }
     }
 
     pub fn from_bytes(bytes: &[u8; 16]) -> Self {
         Self { data: *bytes }
     }
 
+    /// Computes the USID of the voicebank at `path` by hashing its configuration
+    /// files and portrait/icon images.
+    pub fn from_voicebank(path: impl AsRef<Path>) -> Result<Self> {
+        Self::from_voicebank_with(path, &HashOptions::default())
+    }
+
+    /// Like [`USID::from_voicebank`], but with custom formats or settings.
+    pub fn from_voicebank_with(path: impl AsRef<Path>, options: &HashOptions) -> Result<Self> {
+        Self::from_files(&VoicebankFiles::from_dir(path)?, options)
+    }
+
+    /// Computes the USID of a voicebank inside a ZIP-based archive (`.zip`, `.uar`,
+    /// `.vogen`) without extracting it. The result matches [`USID::from_voicebank`]
+    /// on the extracted voicebank directory.
+    #[cfg(feature = "archive")]
+    pub fn from_archive(reader: impl std::io::Read + std::io::Seek + Send + 'static) -> Result<Self> {
+        Self::from_archive_with(reader, &HashOptions::default())
+    }
+
+    /// Like [`USID::from_archive`], but with custom formats or settings.
+    #[cfg(feature = "archive")]
+    pub fn from_archive_with(
+        reader: impl std::io::Read + std::io::Seek + Send + 'static,
+        options: &HashOptions,
+    ) -> Result<Self> {
+        Self::from_files(&VoicebankFiles::from_archive(reader)?, options)
+    }
+
+    /// Computes the USID of an already listed voicebank.
+    pub fn from_files(files: &VoicebankFiles, options: &HashOptions) -> Result<Self> {
+        Ok(UsidManifest::from_files(files, options)?.usid)
+    }
+
+    /// Recomputes the USID of the voicebank at `path` with this USID's hash
+    /// algorithm, and fails with [`UsidError::ChecksumMismatch`] if it differs.
+    /// Fails with [`UsidError::NotContentDerived`] for random and name-based USIDs,
+    /// which no voicebank hashes to.
+    ///
+    /// Hashes with the default [`HashOptions`]. The USID does not record whether
+    /// [`HashOptions::fingerprint_audio`] was set, so USIDs computed with it must
+    /// be checked with [`USID::verify_files`] instead.
+    pub fn verify(&self, path: impl AsRef<Path>) -> Result<()> {
+        self.verify_files(&VoicebankFiles::from_dir(path)?, &HashOptions::default())
+    }
+
+    /// Like [`USID::verify`], for an already listed voicebank. The algorithm set
+    /// in `options` is ignored in favour of this USID's.
+    pub fn verify_files(&self, files: &VoicebankFiles, options: &HashOptions) -> Result<()> {
+        let version = self.validate()?;
+        let (Version::Content, Some(algorithm)) = (version, self.algorithm()) else {
+            return Err(UsidError::NotContentDerived(version));
+        };
+
+        let found = voicebank::manifest(files, options, algorithm)?.usid;
+        if found != *self {
+            return Err(UsidError::ChecksumMismatch { expected: *self, found });
+        }
+
+        Ok(())
+    }
+
+    /// Parses the canonical text form produced by [`USID::as_string`]:
+    /// the `usid:` prefix followed by 32 lowercase hex digits in dash-separated
+    /// groups of four. Uppercase digits are rejected, so every USID has exactly
+    /// one text form.
     pub fn from_string(s: &str) -> Result<Self> {
-        let data = if s.starts_with("usid:") {
-            // Extract the fallback UUID and data from the string
-            let parts: Vec<&str> = s.split(':').collect();
-            if parts.len() < 2 {
-                bail!("Invalid USID format")
-            }
-            let hash = parts[1];
-            hash
-        } else {
-            &s
+        let Some(body) = s.strip_prefix(PREFIX) else {
+            return Err(UsidError::InvalidPrefix);
         };
 
-        let undashed = data.replace("-", "");
+        if s.len() != ENCODED_LEN {
+            return Err(UsidError::InvalidLength { expected: ENCODED_LEN, found: s.len() });
+        }
+
+        let mut data = [0; 16];
+        let mut nibble = 0;
+        for (i, c) in body.char_indices() {
+            let position = PREFIX.len() + i;
+
+            // Every fifth character separates two groups
+            if i % (GROUP_LEN + 1) == GROUP_LEN {
+                if c != '-' {
+                    return Err(UsidError::InvalidCharacter { position, character: c });
+                }
+                continue;
+            }
+
+            let value = match c {
+                '0'..='9' => c as u8 - b'0',
+                'a'..='f' => c as u8 - b'a' + 10,
+                _ => return Err(UsidError::InvalidCharacter { position, character: c }),
+            };
+            data[nibble / 2] |= value << if nibble % 2 == 0 { 4 } else { 0 };
+            nibble += 1;
+        }
 
-        let data: [u8; 16] = undashed.as_bytes()[..16].try_into()?;
         Ok(Self { data })
     }
 
+    /// Like [`USID::from_string`], but rejects USIDs whose version this crate does not know.
+    pub fn from_string_checked(s: &str) -> Result<Self> {
+        let usid = Self::from_string(s)?;
+        usid.validate()?;
+        Ok(usid)
+    }
+
+    /// Renders the canonical text form, e.g. `usid:0123-4567-89ab-cdef-0123-4567-89ab-cdef`.
     pub fn as_string(&self) -> String {
-        let str = String::from_utf8_lossy(&self.data);
+        let hex = self.data.iter().map(|b| format!("{:02x}", b)).collect::<String>();
 
         // Insert dashes every 4 characters
-        let dashed = str.chars().enumerate().map(|(i, c)| {
-            if i > 0 && i % 4 == 0 {
+        let dashed = hex.chars().enumerate().map(|(i, c)| {
+            if i > 0 && i % GROUP_LEN == 0 {
                 format!("-{}", c)
             } else {
                 c.to_string()
             }
         }).collect::<String>();
 
-        format!("usid:{}", dashed)
+        format!("{}{}", PREFIX, dashed)
     }
 
     pub fn as_uuid(&self) -> Uuid {
@@ -65,6 +231,41 @@ impl USID {
     pub fn is_empty(&self) -> bool {
         self.data == [0; 16]
     }
+
+    /// Returns the scheme this USID was produced with, or `None` if its
+    /// version or variant bits are not recognised.
+    pub fn version(&self) -> Option<Version> {
+        if self.is_empty() {
+            return Some(Version::Nil);
+        }
+
+        if !version::has_variant(&self.data) {
+            return None;
+        }
+
+        Version::from_u8(version::raw_version(&self.data)).filter(|v| *v != Version::Nil)
+    }
+
+    /// Returns the hash function a content-derived USID was computed with, or
+    /// `None` for other versions.
+    pub fn algorithm(&self) -> Option<HashAlgorithm> {
+        if self.version() != Some(Version::Content) {
+            return None;
+        }
+
+        HashAlgorithm::from_u8(version::raw_algorithm(&self.data))
+    }
+
+    /// Returns the version of this USID, or an error if it was produced by an
+    /// unknown scheme or hash algorithm.
+    pub fn validate(&self) -> Result<Version> {
+        let version = self.version().ok_or(UsidError::UnsupportedVersion(version::raw_version(&self.data)))?;
+        if version == Version::Content && self.algorithm().is_none() {
+            return Err(UsidError::UnsupportedAlgorithm(version::raw_algorithm(&self.data)));
+        }
+
+        Ok(version)
+    }
 }
 
 // Implement a serde serializer for USID
@@ -91,14 +292,104 @@ impl<'de> Deserialize<'de> for USID {
     }
 }
 
-impl PartialEq for USID {
-    fn eq(&self, other: &Self) -> bool {
-        self.data == other.data
-    }
-}
-
 impl Display for USID {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", self.as_string())
     }
-}
\ No newline at end of file
+}
+
+impl FromStr for USID {
+    type Err = UsidError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        USID::from_string(s)
+    }
+}
+
+impl TryFrom<&str> for USID {
+    type Error = UsidError;
+
+    fn try_from(s: &str) -> Result<Self> {
+        USID::from_string(s)
+    }
+}
+
+impl TryFrom<&[u8]> for USID {
+    type Error = UsidError;
+
+    fn try_from(bytes: &[u8]) -> Result<Self> {
+        let data: [u8; 16] = bytes.try_into()
+            .map_err(|_| UsidError::InvalidLength { expected: 16, found: bytes.len() })?;
+        Ok(Self { data })
+    }
+}
+
+impl From<Uuid> for USID {
+    fn from(uuid: Uuid) -> Self {
+        USID::from_uuid(uuid)
+    }
+}
+
+impl From<USID> for Uuid {
+    fn from(usid: USID) -> Self {
+        usid.as_uuid()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn namespaces_are_derived_from_their_names() {
+        let namespaces = [
+            (USID::NAMESPACE_OPENVB, "openvb"),
+            (USID::NAMESPACE_UTAU, "utau"),
+            (USID::NAMESPACE_OPENUTAU, "openutau"),
+            (USID::NAMESPACE_DIFFSINGER, "diffsinger"),
+            (USID::NAMESPACE_ENUNU, "enunu"),
+        ];
+
+        for (namespace, name) in namespaces {
+            let derived = Uuid::new_v5(&Uuid::NAMESPACE_URL, format!("usid:namespace:{}", name).as_bytes());
+            assert_eq!(namespace, USID::from_uuid(derived), "{}", name);
+        }
+    }
+
+    #[test]
+    fn text_form_round_trips() {
+        let usids = [
+            USID::new(),
+            USID::random(),
+            USID::from_name(&USID::NAMESPACE_UTAU, "Kasane Teto"),
+            USID::from_bytes(&[0xff; 16]),
+            USID::from_bytes(&[0x01, 0x23, 0x45, 0x67, 0x89, 0xab, 0xcd, 0xef, 0xfe, 0xdc, 0xba, 0x98, 0x76, 0x54, 0x32, 0x10]),
+        ];
+
+        for usid in usids {
+            assert_eq!(USID::from_string(&usid.as_string()).unwrap(), usid);
+        }
+    }
+
+    #[test]
+    fn text_form_is_grouped_lowercase_hex() {
+        let usid = USID::from_bytes(&[0x01, 0x23, 0x45, 0x67, 0x89, 0xab, 0xcd, 0xef, 0xfe, 0xdc, 0xba, 0x98, 0x76, 0x54, 0x32, 0x10]);
+        assert_eq!(usid.as_string(), "usid:0123-4567-89ab-cdef-fedc-ba98-7654-3210");
+    }
+
+    #[test]
+    fn from_string_rejects_uppercase() {
+        let err = USID::from_string("usid:0123-4567-89AB-cdef-fedc-ba98-7654-3210").unwrap_err();
+        assert!(matches!(err, UsidError::InvalidCharacter { position: 17, character: 'A' }));
+    }
+
+    #[test]
+    fn from_string_rejects_malformed_input() {
+        assert!(matches!(USID::from_string("0123-4567-89ab-cdef-fedc-ba98-7654-3210"), Err(UsidError::InvalidPrefix)));
+        assert!(matches!(USID::from_string("usid:0123"), Err(UsidError::InvalidLength { .. })));
+        assert!(matches!(
+            USID::from_string("usid:0123_4567-89ab-cdef-fedc-ba98-7654-3210"),
+            Err(UsidError::InvalidCharacter { position: 9, character: '_' }),
+        ));
+    }
+}
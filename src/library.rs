@@ -0,0 +1,374 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Result, UsidError};
+use crate::manifest::UsidManifest;
+use crate::options::HashOptions;
+use crate::voicebank::{GenericFormat, IgnoreRules, VoicebankFiles, VoicebankFormat};
+use crate::USID;
+
+/// Files whose presence marks a directory as a voicebank root.
+const MARKER_FILES: &[&str] = &[
+    "character.txt",
+    "character.yaml",
+    "oto.ini",
+    "prefix.map",
+    "dsconfig.yaml",
+    "enuconfig.yaml",
+];
+
+/// Extensions of the ZIP-based archives voicebanks are distributed as.
+#[cfg(feature = "archive")]
+const ARCHIVE_EXTENSIONS: &[&str] = &["zip", "uar", "vogen"];
+
+/// The USIDs of every voicebank below a library folder.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct LibraryIndex {
+    pub root: PathBuf,
+    /// The voicebanks found, sorted by path.
+    pub entries: Vec<LibraryEntry>,
+    /// Folders that could not be searched for voicebanks, and why.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub warnings: Vec<String>,
+}
+
+/// A voicebank found while scanning a library.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct LibraryEntry {
+    /// The voicebank directory or archive.
+    pub path: PathBuf,
+    /// Display name from `character.yaml` or `character.txt`.
+    pub name: Option<String>,
+    /// Name of the [`VoicebankFormat`](crate::VoicebankFormat) the voicebank was detected as.
+    pub format: Option<String>,
+    /// `None` if the USID could not be computed; the reason is among the warnings.
+    pub usid: Option<USID>,
+    /// Shared by every release of the voicebank, see [`UsidManifest::family`].
+    pub family: Option<USID>,
+    pub warnings: Vec<String>,
+    /// The manifest behind the USID, used to find near-duplicates. Not serialised.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub manifest: Option<UsidManifest>,
+}
+
+/// Reported after each voicebank of a scan has been hashed.
+#[derive(Clone, Copy, Debug)]
+pub struct ScanProgress<'a> {
+    /// Number of voicebanks hashed so far, including this one.
+    pub completed: usize,
+    /// Number of voicebanks found below the library root.
+    pub total: usize,
+    pub path: &'a Path,
+}
+
+impl LibraryIndex {
+    /// Finds every voicebank below `root` and computes its USID.
+    pub fn scan(root: impl AsRef<Path>, options: &HashOptions) -> Result<Self> {
+        Self::scan_with_progress(root, options, |_| {})
+    }
+
+    /// Like [`LibraryIndex::scan`], calling `progress` after each voicebank, e.g.
+    /// to drive a progress bar. A voicebank that cannot be hashed does not fail
+    /// the scan but is listed without a USID.
+    pub fn scan_with_progress(
+        root: impl AsRef<Path>,
+        options: &HashOptions,
+        mut progress: impl FnMut(ScanProgress<'_>),
+    ) -> Result<Self> {
+        let root = root.as_ref();
+        let (paths, warnings) = Self::find_voicebanks(root)?;
+
+        let mut entries = Vec::with_capacity(paths.len());
+        for (i, path) in paths.iter().enumerate() {
+            entries.push(index_entry(path, options));
+            progress(ScanProgress { completed: i + 1, total: paths.len(), path });
+        }
+
+        Ok(Self { root: root.to_path_buf(), entries, warnings })
+    }
+
+    /// Lists the voicebanks below `root` without hashing them: every directory
+    /// containing a character, oto or engine configuration file, and with the
+    /// `archive` feature every `.zip`, `.uar` and `.vogen` file. Folders inside
+    /// a voicebank are not searched any further, and symlinked folders are only
+    /// searched once. Folders that cannot be read are skipped with a warning.
+    fn find_voicebanks(root: &Path) -> Result<(Vec<PathBuf>, Vec<String>)> {
+        if !root.is_dir() {
+            return Err(UsidError::NotADirectory(root.to_path_buf()));
+        }
+
+        let mut search = Search {
+            root,
+            rules: IgnoreRules::defaults(),
+            visited: HashSet::new(),
+            found: Vec::new(),
+            warnings: Vec::new(),
+        };
+        search.find(root)?;
+        search.found.sort();
+        Ok((search.found, search.warnings))
+    }
+
+    pub fn entry(&self, path: impl AsRef<Path>) -> Option<&LibraryEntry> {
+        self.entries.iter().find(|e| e.path == path.as_ref())
+    }
+
+    /// Finds the voicebank a USID refers to, along with the subbank if it is a
+    /// [child](USID::child) USID.
+    pub fn resolve(&self, usid: &USID) -> Option<(&LibraryEntry, Option<&str>)> {
+        if let Some(entry) = self.entries.iter().find(|e| e.usid.as_ref() == Some(usid)) {
+            return Some((entry, None));
+        }
+
+        self.entries.iter().find_map(|entry| {
+            let subbank = entry.manifest.as_ref()?.subbank(usid)?;
+            Some((entry, Some(subbank)))
+        })
+    }
+
+    /// Renders the index as CSV with the columns `path`, `name`, `format`, `usid`,
+    /// `family` and `warnings`. Warnings are joined with `; `.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("path,name,format,usid,family,warnings\n");
+        for entry in &self.entries {
+            let fields = [
+                entry.path.to_string_lossy().into_owned(),
+                entry.name.clone().unwrap_or_default(),
+                entry.format.clone().unwrap_or_default(),
+                entry.usid.map(|u| u.to_string()).unwrap_or_default(),
+                entry.family.map(|u| u.to_string()).unwrap_or_default(),
+                entry.warnings.join("; "),
+            ];
+
+            let row = fields.iter().map(|f| csv_field(f)).collect::<Vec<_>>().join(",");
+            csv.push_str(&row);
+            csv.push('\n');
+        }
+        csv
+    }
+}
+
+/// State of a search for voicebanks below a library root.
+struct Search<'a> {
+    root: &'a Path,
+    rules: IgnoreRules,
+    /// Canonical paths of the folders searched so far, so symlink loops end.
+    visited: HashSet<PathBuf>,
+    found: Vec<PathBuf>,
+    warnings: Vec<String>,
+}
+
+impl Search<'_> {
+    fn find(&mut self, dir: &Path) -> Result<()> {
+        let canonical = fs::canonicalize(dir).map_err(|e| UsidError::io(dir, e))?;
+        if !self.visited.insert(canonical) {
+            return Ok(());
+        }
+
+        let mut subdirs = Vec::new();
+        let mut archives = Vec::new();
+        let mut is_voicebank = false;
+
+        for entry in fs::read_dir(dir).map_err(|e| UsidError::io(dir, e))? {
+            let entry = entry.map_err(|e| UsidError::io(dir, e))?;
+            let path = entry.path();
+            let is_dir = path.is_dir();
+
+            let relative = path.strip_prefix(self.root).unwrap_or(&path).to_string_lossy().replace('\\', "/");
+            if self.rules.is_ignored(&relative, is_dir) {
+                continue;
+            }
+
+            let name = entry.file_name().to_string_lossy().to_lowercase();
+            if is_dir {
+                subdirs.push(path);
+            } else if MARKER_FILES.contains(&name.as_str()) {
+                is_voicebank = true;
+            } else if is_archive(&name) {
+                archives.push(path);
+            }
+        }
+
+        // Archives inside a voicebank are part of it, e.g. a bundled backup
+        if is_voicebank {
+            self.found.push(dir.to_path_buf());
+            return Ok(());
+        }
+        self.found.extend(archives);
+
+        for subdir in subdirs {
+            // An unreadable folder should not abort a scan of the whole library
+            if let Err(e) = self.find(&subdir) {
+                self.warnings.push(e.to_string());
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "archive")]
+fn is_archive(name: &str) -> bool {
+    name.rsplit_once('.').is_some_and(|(_, ext)| ARCHIVE_EXTENSIONS.contains(&ext))
+}
+
+#[cfg(not(feature = "archive"))]
+fn is_archive(_name: &str) -> bool {
+    false
+}
+
+fn index_entry(path: &Path, options: &HashOptions) -> LibraryEntry {
+    let mut entry = LibraryEntry {
+        path: path.to_path_buf(),
+        name: None,
+        format: None,
+        usid: None,
+        family: None,
+        warnings: Vec::new(),
+        manifest: None,
+    };
+
+    let files = match VoicebankFiles::open(path) {
+        Ok(files) => files,
+        Err(e) => {
+            entry.warnings.push(e.to_string());
+            return entry;
+        }
+    };
+
+    entry.name = files.name();
+    if entry.name.is_none() {
+        entry.warnings.push("No name in character.yaml or character.txt".to_string());
+    }
+
+    match UsidManifest::from_files(&files, options) {
+        Ok(manifest) => {
+            if manifest.format == GenericFormat.name() {
+                entry.warnings.push("Format not recognised, only generic metadata files were hashed".to_string());
+            }
+            entry.format = Some(manifest.format.clone());
+            entry.usid = Some(manifest.usid);
+            entry.family = manifest.family;
+            entry.manifest = Some(manifest);
+        }
+        Err(e) => entry.warnings.push(e.to_string()),
+    }
+
+    entry
+}
+
+/// Quotes a CSV field if it contains a delimiter, quote or line break.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn touch(root: &Path, files: &[&str]) {
+        for relative in files {
+            let path = root.join(relative);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, relative).unwrap();
+        }
+    }
+
+    fn found(root: &Path) -> Vec<PathBuf> {
+        let (found, warnings) = LibraryIndex::find_voicebanks(root).unwrap();
+        assert!(warnings.is_empty(), "{:?}", warnings);
+        found.iter().map(|p| p.strip_prefix(root).unwrap().to_path_buf()).collect()
+    }
+
+    #[test]
+    fn marker_files_identify_voicebanks() {
+        let dir = tempfile::tempdir().unwrap();
+        touch(dir.path(), &[
+            "utau/Teto/character.txt",
+            "utau/Ted/oto.ini",
+            "openutau/Ritsu/character.yaml",
+            "diffsinger/Opencpop/dsconfig.yaml",
+            "enunu/Natsume/enuconfig.yaml",
+            "notes/readme.txt",
+        ]);
+
+        assert_eq!(found(dir.path()), [
+            "diffsinger/Opencpop",
+            "enunu/Natsume",
+            "openutau/Ritsu",
+            "utau/Ted",
+            "utau/Teto",
+        ].map(PathBuf::from));
+    }
+
+    #[test]
+    fn voicebanks_are_not_searched_further() {
+        let dir = tempfile::tempdir().unwrap();
+        touch(dir.path(), &["Teto/character.txt", "Teto/A3/oto.ini", "Teto/backup/Teto/character.txt"]);
+        assert_eq!(found(dir.path()), [PathBuf::from("Teto")]);
+    }
+
+    #[test]
+    fn ignored_folders_are_not_searched() {
+        let dir = tempfile::tempdir().unwrap();
+        touch(dir.path(), &["__MACOSX/Teto/character.txt", "Teto/character.txt"]);
+        assert_eq!(found(dir.path()), [PathBuf::from("Teto")]);
+    }
+
+    #[cfg(feature = "archive")]
+    #[test]
+    fn archives_inside_a_voicebank_are_part_of_it() {
+        let dir = tempfile::tempdir().unwrap();
+        touch(dir.path(), &["Teto/character.txt", "Teto/backup.zip", "downloads/Ted.uar", "downloads/notes.txt"]);
+        assert_eq!(found(dir.path()), ["Teto", "downloads/Ted.uar"].map(PathBuf::from));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlink_loops_are_searched_once() {
+        let dir = tempfile::tempdir().unwrap();
+        touch(dir.path(), &["banks/Teto/character.txt"]);
+        std::os::unix::fs::symlink(dir.path(), dir.path().join("banks/loop")).unwrap();
+        assert_eq!(found(dir.path()), [PathBuf::from("banks/Teto")]);
+    }
+
+    #[test]
+    fn missing_root_is_an_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let missing = dir.path().join("missing");
+        assert!(matches!(LibraryIndex::find_voicebanks(&missing), Err(UsidError::NotADirectory(_))));
+    }
+
+    #[test]
+    fn csv_quotes_fields_that_need_it() {
+        let index = LibraryIndex {
+            root: PathBuf::from("library"),
+            entries: vec![LibraryEntry {
+                path: PathBuf::from("library/Teto, Kasane"),
+                name: Some("Teto \"Kasane\"".to_string()),
+                format: Some("utau".to_string()),
+                usid: None,
+                family: None,
+                warnings: vec!["first".to_string(), "second\nline".to_string()],
+                manifest: None,
+            }],
+            warnings: Vec::new(),
+        };
+
+        assert_eq!(
+            index.to_csv(),
+            "path,name,format,usid,family,warnings\n\
+             \"library/Teto, Kasane\",\"Teto \"\"Kasane\"\"\",utau,,,\"first; second\nline\"\n",
+        );
+    }
+}
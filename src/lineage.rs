@@ -0,0 +1,65 @@
+use crate::library::{LibraryEntry, LibraryIndex};
+use crate::manifest::UsidManifest;
+use crate::voicebank::{DiffSingerFormat, EnunuFormat, UtauFormat, VoicebankFiles, VoicebankFormat};
+use crate::USID;
+
+/// Metadata key under which a voicebank may declare its family USID.
+const FAMILY_KEY: &str = "usid";
+
+/// Determines the family USID of a voicebank: the USID declared under `usid` in
+/// its `character.yaml` or `character.txt` if valid, otherwise one derived from
+/// its author and name within the namespace of its format.
+pub(crate) fn family(files: &VoicebankFiles, format: &str) -> Option<USID> {
+    if let Some(declared) = files.metadata(FAMILY_KEY).and_then(|s| USID::from_string_checked(&s).ok()) {
+        return Some(declared);
+    }
+
+    let namespace = if format == UtauFormat.name() {
+        USID::NAMESPACE_UTAU
+    } else if format == DiffSingerFormat.name() {
+        USID::NAMESPACE_DIFFSINGER
+    } else if format == EnunuFormat.name() {
+        USID::NAMESPACE_ENUNU
+    } else {
+        USID::NAMESPACE_OPENVB
+    };
+
+    let name = files.name()?;
+    Some(match files.metadata("author") {
+        Some(author) => USID::from_author(&namespace, &author, &name),
+        None => USID::from_name(&namespace, &name),
+    })
+}
+
+impl UsidManifest {
+    /// Reports whether this manifest and `other` are releases of the same voicebank.
+    pub fn same_family(&self, other: &UsidManifest) -> bool {
+        self.family.is_some() && self.family == other.family
+    }
+}
+
+impl LibraryIndex {
+    /// Returns the family of the voicebank a USID refers to. `usid` may be a
+    /// content USID, a [child](USID::child) USID or a family USID itself.
+    pub fn family_of(&self, usid: &USID) -> Option<USID> {
+        if self.entries.iter().any(|e| e.family.as_ref() == Some(usid)) {
+            return Some(*usid);
+        }
+
+        self.resolve(usid).and_then(|(entry, _)| entry.family)
+    }
+
+    /// Reports whether two USIDs refer to releases of the same voicebank, e.g.
+    /// to match a project file written against an older release.
+    pub fn same_family(&self, a: &USID, b: &USID) -> bool {
+        match (self.family_of(a), self.family_of(b)) {
+            (Some(a), Some(b)) => a == b,
+            _ => false,
+        }
+    }
+
+    /// Lists the installed releases of a family, sorted by path.
+    pub fn releases(&self, family: &USID) -> Vec<&LibraryEntry> {
+        self.entries.iter().filter(|e| e.family.as_ref() == Some(family)).collect()
+    }
+}
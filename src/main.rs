@@ -0,0 +1,318 @@
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::Arc;
+
+use clap::{Args, Parser, Subcommand};
+use serde_json::json;
+use usid::{DigestCache, DuplicateKind, HashAlgorithm, HashOptions, LibraryIndex, UsidDigest, UsidError, UsidManifest, VoicebankFiles, USID};
+
+/// Exit code for a voicebank that does not match the expected USID.
+const EXIT_MISMATCH: u8 = 1;
+/// Exit code for invalid input or a voicebank that could not be read.
+const EXIT_ERROR: u8 = 2;
+
+/// Compute, inspect and verify Universal Singer IDentifiers.
+#[derive(Parser)]
+#[command(name = "usid", version)]
+struct Cli {
+    /// Print machine-readable JSON instead of text.
+    #[arg(long, global = true)]
+    json: bool,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Print the USID of a voicebank directory or archive.
+    Compute {
+        path: PathBuf,
+        #[command(flatten)]
+        hash: HashArgs,
+    },
+    /// Validate a USID and decode its version, algorithm and bytes.
+    Parse {
+        usid: String,
+    },
+    /// Check that a voicebank still has the given USID or full digest.
+    Verify {
+        path: PathBuf,
+        /// A USID (`usid:...`) or a full digest (`blake3:...`).
+        expected: String,
+        #[command(flatten)]
+        hash: HashArgs,
+    },
+    /// Find every voicebank below a folder and index their USIDs.
+    Scan {
+        root: PathBuf,
+        /// Print the index as CSV.
+        #[arg(long, conflicts_with = "json")]
+        csv: bool,
+        /// Do not report progress on stderr.
+        #[arg(long, short)]
+        quiet: bool,
+        #[command(flatten)]
+        hash: HashArgs,
+    },
+    /// Find voicebanks below a folder that are copies of each other.
+    Duplicates {
+        root: PathBuf,
+        /// Only report copies of this voicebank directory or archive.
+        #[arg(long)]
+        of: Option<PathBuf>,
+        /// Do not report progress on stderr.
+        #[arg(long, short)]
+        quiet: bool,
+        #[command(flatten)]
+        hash: HashArgs,
+    },
+}
+
+#[derive(Args)]
+struct HashArgs {
+    /// Hash function to use: blake3, sha256 or xxh3. `verify` takes it from the expected USID.
+    #[arg(long, value_parser = parse_algorithm)]
+    algorithm: Option<HashAlgorithm>,
+    /// Also fingerprint every WAV sample. Pass it to `verify` too if the USID was computed with it.
+    #[arg(long)]
+    fingerprint_audio: bool,
+    /// Number of threads to hash files on.
+    #[arg(long)]
+    threads: Option<usize>,
+    /// Cache file for per-file digests, created if missing.
+    #[arg(long)]
+    cache: Option<PathBuf>,
+}
+
+/// The outcome of a command that ran to completion.
+enum Outcome {
+    Ok,
+    Mismatch,
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let result = match cli.command {
+        Command::Compute { path, hash } => compute(&path, &hash, cli.json),
+        Command::Parse { usid } => parse(&usid, cli.json),
+        Command::Verify { path, expected, hash } => verify(&path, &expected, &hash, cli.json),
+        Command::Scan { root, csv, quiet, hash } => scan(&root, &hash, csv, quiet, cli.json),
+        Command::Duplicates { root, of, quiet, hash } => duplicates(&root, of.as_deref(), &hash, quiet, cli.json),
+    };
+
+    match result {
+        Ok(Outcome::Ok) => ExitCode::SUCCESS,
+        Ok(Outcome::Mismatch) => ExitCode::from(EXIT_MISMATCH),
+        Err(e) => {
+            if cli.json {
+                println!("{}", json!({ "error": e.to_string() }));
+            } else {
+                eprintln!("error: {}", e);
+            }
+            ExitCode::from(EXIT_ERROR)
+        }
+    }
+}
+
+fn compute(path: &Path, args: &HashArgs, json: bool) -> usid::Result<Outcome> {
+    let manifest = manifest(path, args, args.algorithm.unwrap_or_default())?;
+
+    if json {
+        let subbanks = manifest.children()
+            .map(|(name, usid)| (name.to_string(), json!(usid)))
+            .collect::<serde_json::Map<_, _>>();
+        println!("{}", json!({
+            "path": path,
+            "usid": manifest.usid,
+            "digest": manifest.digest(),
+            "format": manifest.format,
+            "algorithm": manifest.algorithm,
+            "family": manifest.family,
+            "subbanks": subbanks,
+        }));
+    } else {
+        println!("{}", manifest.usid);
+        for (name, usid) in manifest.children() {
+            println!("    {}  {}", usid, name);
+        }
+    }
+
+    Ok(Outcome::Ok)
+}
+
+fn parse(s: &str, json: bool) -> usid::Result<Outcome> {
+    let usid = USID::from_string(s)?;
+    let version = usid.validate()?;
+    let bytes = usid.data.iter().map(|b| format!("{:02x}", b)).collect::<String>();
+
+    if json {
+        println!("{}", json!({
+            "usid": usid,
+            "version": version,
+            "algorithm": usid.algorithm(),
+            "bytes": bytes,
+            "uuid": usid.as_uuid(),
+        }));
+    } else {
+        println!("version:   {:?}", version);
+        if let Some(algorithm) = usid.algorithm() {
+            println!("algorithm: {}", algorithm);
+        }
+        println!("bytes:     {}", bytes);
+        println!("uuid:      {}", usid.as_uuid());
+    }
+
+    Ok(Outcome::Ok)
+}
+
+fn verify(path: &Path, expected: &str, args: &HashArgs, json: bool) -> usid::Result<Outcome> {
+    // The algorithm comes from the expected USID or digest, not the options
+    let algorithm = args.algorithm.unwrap_or_default();
+    let (expected, result) = if expected.starts_with("usid:") {
+        let expected = USID::from_string(expected)?;
+        let result = with_options(args, algorithm, |options| {
+            Ok(expected.verify_files(&VoicebankFiles::open(path)?, options))
+        })?;
+        (expected.to_string(), result)
+    } else {
+        let expected = expected.parse::<UsidDigest>()?;
+        let result = with_options(args, algorithm, |options| {
+            Ok(expected.verify_files(&VoicebankFiles::open(path)?, options))
+        })?;
+        (expected.to_string(), result)
+    };
+
+    let found = match result {
+        Ok(()) => expected.clone(),
+        Err(UsidError::ChecksumMismatch { found, .. }) => found.to_string(),
+        Err(UsidError::DigestMismatch { found, .. }) => found.to_string(),
+        Err(e) => return Err(e),
+    };
+    let matches = found == expected;
+
+    if json {
+        println!("{}", json!({
+            "path": path,
+            "expected": expected,
+            "found": found,
+            "matches": matches,
+        }));
+    } else if matches {
+        println!("OK {}", found);
+    } else {
+        println!("MISMATCH expected {}, found {}", expected, found);
+    }
+
+    Ok(if matches { Outcome::Ok } else { Outcome::Mismatch })
+}
+
+fn scan(root: &Path, args: &HashArgs, csv: bool, quiet: bool, json: bool) -> usid::Result<Outcome> {
+    let index = library(root, args, quiet)?;
+
+    if json {
+        println!("{}", json!(index));
+    } else if csv {
+        print!("{}", index.to_csv());
+        for warning in &index.warnings {
+            eprintln!("warning: {}", warning);
+        }
+    } else {
+        for warning in &index.warnings {
+            println!("warning: {}", warning);
+        }
+        for entry in &index.entries {
+            let usid = entry.usid.map(|u| u.to_string()).unwrap_or_else(|| "-".to_string());
+            println!("{}  {}", usid, entry.path.display());
+            for warning in &entry.warnings {
+                println!("    warning: {}", warning);
+            }
+        }
+    }
+
+    Ok(Outcome::Ok)
+}
+
+fn duplicates(root: &Path, of: Option<&Path>, args: &HashArgs, quiet: bool, json: bool) -> usid::Result<Outcome> {
+    let index = library(root, args, quiet)?;
+
+    let Some(of) = of else {
+        let groups = index.duplicates();
+        if json {
+            println!("{}", json!(groups));
+        } else {
+            for group in &groups {
+                println!("{}:", kind_name(group.kind));
+                for path in &group.paths {
+                    println!("    {}", path.display());
+                }
+            }
+        }
+        return Ok(Outcome::Ok);
+    };
+
+    let manifest = manifest(of, args, args.algorithm.unwrap_or_default())?;
+    let matches = index.duplicates_of(of, &manifest);
+    if json {
+        let matches = matches.iter()
+            .map(|(entry, kind)| json!({ "path": entry.path, "usid": entry.usid, "kind": kind }))
+            .collect::<Vec<_>>();
+        println!("{}", json!(matches));
+    } else {
+        for (entry, kind) in &matches {
+            println!("{}  {}", kind_name(*kind), entry.path.display());
+        }
+    }
+
+    Ok(Outcome::Ok)
+}
+
+fn kind_name(kind: DuplicateKind) -> &'static str {
+    match kind {
+        DuplicateKind::Identical => "identical",
+        DuplicateKind::Similar => "similar",
+    }
+}
+
+/// Scans a library, reporting progress on stderr unless `quiet` is set.
+fn library(root: &Path, args: &HashArgs, quiet: bool) -> usid::Result<LibraryIndex> {
+    with_options(args, args.algorithm.unwrap_or_default(), |options| {
+        LibraryIndex::scan_with_progress(root, options, |progress| {
+            if !quiet {
+                eprintln!("[{}/{}] {}", progress.completed, progress.total, progress.path.display());
+            }
+        })
+    })
+}
+
+fn manifest(path: &Path, args: &HashArgs, algorithm: HashAlgorithm) -> usid::Result<UsidManifest> {
+    with_options(args, algorithm, |options| UsidManifest::from_files(&VoicebankFiles::open(path)?, options))
+}
+
+/// Runs `f` with the hash options given on the command line, saving the digest
+/// cache afterwards if one was requested.
+fn with_options<T>(
+    args: &HashArgs,
+    algorithm: HashAlgorithm,
+    f: impl FnOnce(&HashOptions) -> usid::Result<T>,
+) -> usid::Result<T> {
+    let cache = args.cache.as_ref().map(DigestCache::load).transpose()?.map(Arc::new);
+    let mut options = HashOptions {
+        algorithm,
+        fingerprint_audio: args.fingerprint_audio,
+        cache: cache.clone(),
+        ..Default::default()
+    };
+    if let Some(threads) = args.threads {
+        options = options.with_threads(threads)?;
+    }
+
+    let result = f(&options)?;
+    if let (Some(cache), Some(file)) = (cache, &args.cache) {
+        cache.save(file)?;
+    }
+    Ok(result)
+}
+
+fn parse_algorithm(s: &str) -> Result<HashAlgorithm, String> {
+    s.parse().map_err(|e: UsidError| e.to_string())
+}
@@ -0,0 +1,171 @@
+use std::fmt::Display;
+use std::path::Path;
+use std::str::FromStr;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::digest::UsidDigest;
+use crate::error::{Result, UsidError};
+use crate::hash::HashAlgorithm;
+use crate::options::HashOptions;
+use crate::voicebank::{self, VoicebankFiles};
+use crate::USID;
+
+/// Revision of the file selection and hashing pipeline that produced a manifest.
+pub const ALGORITHM_VERSION: u32 = 5;
+
+/// A record of everything that went into a content-derived USID, so that two
+/// manifests of the "same" voicebank can be compared to see why their IDs differ.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct UsidManifest {
+    pub usid: USID,
+    /// Name of the [`VoicebankFormat`](crate::VoicebankFormat) the voicebank was detected as.
+    pub format: String,
+    /// Hash function behind the file digests and the USID.
+    pub algorithm: HashAlgorithm,
+    pub algorithm_version: u32,
+    /// The identity files, sorted by path.
+    pub files: Vec<ManifestEntry>,
+    /// Names of the voicebank's subbanks, see [`USID::child`].
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Vec::is_empty"))]
+    pub subbanks: Vec<String>,
+    /// Identifies the voicebank across releases, unlike [`UsidManifest::usid`]
+    /// which changes with its contents. Declared as `usid` in `character.yaml`
+    /// or `character.txt`, or else derived from the author and name. `None` if
+    /// the voicebank declares neither.
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Option::is_none"))]
+    pub family: Option<USID>,
+}
+
+/// A single identity file of a voicebank.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ManifestEntry {
+    /// Path relative to the voicebank root, always `/`-separated.
+    pub path: String,
+    /// Size of the file as stored, in bytes.
+    pub size: u64,
+    /// Hex-encoded digest of the file as stored, using the manifest's algorithm.
+    pub digest: String,
+    /// Hex-encoded digest of the file after normalisation. This is what
+    /// contributes to the USID.
+    pub canonical_digest: String,
+    pub normalization: Normalization,
+    /// Hex-encoded 64-bit perceptual hash of images, for spotting near matches
+    /// when an image was re-compressed lossily.
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Option::is_none"))]
+    pub perceptual_hash: Option<String>,
+    /// Role of an image referenced by the character metadata, e.g. `image:portrait`.
+    /// Such images contribute to the USID under their role instead of their path.
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Option::is_none"))]
+    pub role: Option<String>,
+}
+
+impl ManifestEntry {
+    /// The name the file is identified by: its role if it has one, else its path.
+    pub fn key(&self) -> &str {
+        self.role.as_deref().unwrap_or(&self.path)
+    }
+}
+
+/// The normalisation applied to an identity file before hashing.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Normalization {
+    /// Hashed byte for byte.
+    None,
+    /// Decoded from UTF-8 or Shift-JIS, with line endings, surrounding whitespace
+    /// and blank lines normalised.
+    Text,
+    /// [`Normalization::Text`], with lines sorted.
+    SortedLines,
+    /// [`Normalization::SortedLines`], with `key=value` keys lowercased and trimmed.
+    KeyValue,
+    /// oto.ini entries sorted, numbers in shortest form and defaults filled in.
+    Oto,
+    /// YAML parsed and re-emitted with sorted keys.
+    Yaml,
+    /// Image decoded to 8-bit RGBA pixels, ignoring the colour of fully
+    /// transparent pixels.
+    Image,
+    /// WAV sample reduced to its loudness envelope, see [`HashOptions::fingerprint_audio`].
+    AudioEnvelope,
+    /// A normalisation applied by a custom [`VoicebankFormat`](crate::VoicebankFormat).
+    Custom(String),
+}
+
+impl Display for Normalization {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::None => write!(f, "none"),
+            Self::Text => write!(f, "text"),
+            Self::SortedLines => write!(f, "sorted_lines"),
+            Self::KeyValue => write!(f, "key_value"),
+            Self::Oto => write!(f, "oto"),
+            Self::Yaml => write!(f, "yaml"),
+            Self::Image => write!(f, "image"),
+            Self::AudioEnvelope => write!(f, "audio_envelope"),
+            Self::Custom(name) => write!(f, "custom:{}", name),
+        }
+    }
+}
+
+impl FromStr for Normalization {
+    type Err = UsidError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s {
+            "none" => Self::None,
+            "text" => Self::Text,
+            "sorted_lines" => Self::SortedLines,
+            "key_value" => Self::KeyValue,
+            "oto" => Self::Oto,
+            "yaml" => Self::Yaml,
+            "image" => Self::Image,
+            "audio_envelope" => Self::AudioEnvelope,
+            _ => match s.strip_prefix("custom:") {
+                Some(name) => Self::Custom(name.to_string()),
+                None => return Err(UsidError::UnknownNormalization(s.to_string())),
+            },
+        })
+    }
+}
+
+impl UsidManifest {
+    /// Computes the manifest of the voicebank at `path`.
+    pub fn from_voicebank(path: impl AsRef<Path>) -> Result<Self> {
+        Self::from_files(&VoicebankFiles::from_dir(path)?, &HashOptions::default())
+    }
+
+    /// Computes the manifest of an already listed voicebank.
+    pub fn from_files(files: &VoicebankFiles, options: &HashOptions) -> Result<Self> {
+        voicebank::manifest(files, options, options.algorithm)
+    }
+
+    /// Returns the full-length digest that [`UsidManifest::usid`] was truncated from.
+    pub fn digest(&self) -> UsidDigest {
+        voicebank::digest(&self.files, self.algorithm)
+    }
+
+    pub fn entry(&self, path: &str) -> Option<&ManifestEntry> {
+        self.files.iter().find(|e| e.path == path)
+    }
+
+    /// Finds an entry by its [key](ManifestEntry::key).
+    pub fn entry_by_key(&self, key: &str) -> Option<&ManifestEntry> {
+        self.files.iter().find(|e| e.key() == key)
+    }
+
+    /// The subbanks of the voicebank with their USIDs.
+    pub fn children(&self) -> impl Iterator<Item = (&str, USID)> {
+        self.subbanks.iter().map(|name| (name.as_str(), self.usid.child(name)))
+    }
+
+    /// Returns the name of the subbank `child` identifies, if it belongs to this voicebank.
+    pub fn subbank(&self, child: &USID) -> Option<&str> {
+        self.children().find(|(_, usid)| usid == child).map(|(name, _)| name)
+    }
+}
@@ -0,0 +1,42 @@
+use std::sync::Arc;
+
+use rayon::{ThreadPool, ThreadPoolBuilder};
+
+use crate::cache::DigestCache;
+use crate::error::{Result, UsidError};
+use crate::hash::HashAlgorithm;
+use crate::voicebank::FormatRegistry;
+
+/// Settings for computing content-derived USIDs.
+#[derive(Default)]
+pub struct HashOptions {
+    /// Formats considered when detecting a voicebank's layout.
+    pub formats: FormatRegistry,
+    /// Hash function used for file digests and the USID itself.
+    pub algorithm: HashAlgorithm,
+    /// Also fingerprint every WAV sample, so that voicebanks sharing an oto.ini
+    /// template but with different recordings get different USIDs. Much slower
+    /// than hashing configuration files alone.
+    ///
+    /// A USID does not record whether audio was fingerprinted, so such USIDs
+    /// must be checked with [`USID::verify_files`](crate::USID::verify_files)
+    /// and this option set: [`USID::verify`](crate::USID::verify) uses the
+    /// default options and reports a mismatch.
+    pub fingerprint_audio: bool,
+    /// Thread pool to hash files on, see [`HashOptions::with_threads`]. `None`
+    /// uses the global rayon thread pool.
+    pub thread_pool: Option<Arc<ThreadPool>>,
+    /// Digests of previously hashed files, so unchanged files are not read again.
+    /// Only used for voicebank directories.
+    pub cache: Option<Arc<DigestCache>>,
+}
+
+impl HashOptions {
+    /// Hashes files on a dedicated pool of `threads` threads. The pool is built
+    /// once and shared by every voicebank hashed with these options.
+    pub fn with_threads(mut self, threads: usize) -> Result<Self> {
+        let pool = ThreadPoolBuilder::new().num_threads(threads).build().map_err(UsidError::ThreadPool)?;
+        self.thread_pool = Some(Arc::new(pool));
+        Ok(self)
+    }
+}
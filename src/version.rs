@@ -0,0 +1,157 @@
+use crate::hash::HashAlgorithm;
+
+/// Byte holding the version in its high nibble, as in RFC 9562 UUIDs.
+pub(crate) const VERSION_BYTE: usize = 6;
+/// Byte holding the variant in its two most significant bits.
+pub(crate) const VARIANT_BYTE: usize = 8;
+/// The RFC 9562 variant (`0b10`), shared by every versioned USID.
+const VARIANT_BITS: u8 = 0b1000_0000;
+const VARIANT_MASK: u8 = 0b1100_0000;
+/// Bits of the variant byte that hold the [`HashAlgorithm`] of content-derived USIDs.
+const ALGORITHM_MASK: u8 = 0b0011_0000;
+const ALGORITHM_SHIFT: u8 = 4;
+
+/// The scheme a USID was produced with, encoded in its version bits.
+///
+/// Version numbers follow RFC 9562 where a matching UUID version exists, so a
+/// random UUID passed through [`USID::from_uuid`](crate::USID::from_uuid) reports
+/// [`Version::Random`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Version {
+    /// The all-zero USID.
+    Nil = 0,
+    /// Randomly generated, e.g. for voicebanks still being authored.
+    Random = 4,
+    /// Derived from a namespace and a name.
+    Name = 5,
+    /// Derived from a hash of the voicebank's contents. The two bits following
+    /// the variant bits record the [`HashAlgorithm`] used.
+    Content = 8,
+}
+
+impl Version {
+    pub fn from_u8(version: u8) -> Option<Self> {
+        match version {
+            0 => Some(Self::Nil),
+            4 => Some(Self::Random),
+            5 => Some(Self::Name),
+            8 => Some(Self::Content),
+            _ => None,
+        }
+    }
+
+    pub fn as_u8(&self) -> u8 {
+        *self as u8
+    }
+}
+
+/// Overwrites the version and variant bits of `data`.
+pub(crate) fn stamp(mut data: [u8; 16], version: Version) -> [u8; 16] {
+    data[VERSION_BYTE] = (data[VERSION_BYTE] & 0x0f) | (version.as_u8() << 4);
+    data[VARIANT_BYTE] = (data[VARIANT_BYTE] & !VARIANT_MASK) | VARIANT_BITS;
+    data
+}
+
+/// Overwrites the algorithm bits of `data`, which directly follow the variant bits.
+pub(crate) fn stamp_algorithm(mut data: [u8; 16], algorithm: HashAlgorithm) -> [u8; 16] {
+    data[VARIANT_BYTE] = (data[VARIANT_BYTE] & !ALGORITHM_MASK) | (algorithm.as_u8() << ALGORITHM_SHIFT);
+    data
+}
+
+/// Returns the raw algorithm bits of `data`.
+pub(crate) fn raw_algorithm(data: &[u8; 16]) -> u8 {
+    (data[VARIANT_BYTE] & ALGORITHM_MASK) >> ALGORITHM_SHIFT
+}
+
+/// Returns the raw version nibble of `data`.
+pub(crate) fn raw_version(data: &[u8; 16]) -> u8 {
+    data[VERSION_BYTE] >> 4
+}
+
+/// Reports whether `data` carries the USID variant bits.
+pub(crate) fn has_variant(data: &[u8; 16]) -> bool {
+    data[VARIANT_BYTE] & VARIANT_MASK == VARIANT_BITS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::UsidError;
+    use crate::{UsidDigest, USID};
+
+    #[test]
+    fn random_usids_report_random() {
+        let usid = USID::random();
+        assert_eq!(usid.version(), Some(Version::Random));
+        assert!(has_variant(&usid.data));
+        assert_eq!(usid.validate().unwrap(), Version::Random);
+    }
+
+    #[test]
+    fn name_usids_report_name() {
+        let usid = USID::from_name(&USID::NAMESPACE_UTAU, "Kasane Teto");
+        assert_eq!(usid.version(), Some(Version::Name));
+        assert!(has_variant(&usid.data));
+        assert_eq!(usid.algorithm(), None);
+    }
+
+    #[test]
+    fn nil_usid_reports_nil() {
+        assert_eq!(USID::new().version(), Some(Version::Nil));
+    }
+
+    #[test]
+    fn stamp_keeps_the_other_bits() {
+        let data = stamp([0xff; 16], Version::Name);
+        assert_eq!(data[VERSION_BYTE], 0x5f);
+        assert_eq!(data[VARIANT_BYTE], 0xbf);
+        assert!(data.iter().enumerate().all(|(i, b)| i == VERSION_BYTE || i == VARIANT_BYTE || *b == 0xff));
+    }
+
+    #[test]
+    fn unknown_versions_are_rejected() {
+        let mut data = stamp([0; 16], Version::Random);
+        data[VERSION_BYTE] = 0x70;
+        let usid = USID::from_bytes(&data);
+
+        assert_eq!(usid.version(), None);
+        assert!(matches!(usid.validate(), Err(UsidError::UnsupportedVersion(7))));
+        assert!(matches!(USID::from_string_checked(&usid.as_string()), Err(UsidError::UnsupportedVersion(7))));
+        assert!(USID::from_string(&usid.as_string()).is_ok());
+    }
+
+    #[test]
+    fn missing_variant_bits_are_rejected() {
+        let mut data = stamp([0; 16], Version::Random);
+        data[VARIANT_BYTE] = 0;
+        assert!(matches!(USID::from_bytes(&data).validate(), Err(UsidError::UnsupportedVersion(4))));
+    }
+
+    #[test]
+    fn content_usids_record_their_algorithm() {
+        for algorithm in [HashAlgorithm::Blake3, HashAlgorithm::Sha256, HashAlgorithm::Xxh3] {
+            let digest = UsidDigest::new(algorithm, vec![0xff; algorithm.digest_len()]).unwrap();
+            let usid = digest.to_usid();
+
+            assert_eq!(usid.version(), Some(Version::Content));
+            assert_eq!(usid.algorithm(), Some(algorithm));
+            assert_eq!(raw_algorithm(&usid.data), algorithm.as_u8());
+            assert_eq!(usid.validate().unwrap(), Version::Content);
+            assert_eq!(USID::from_string_checked(&usid.as_string()).unwrap(), usid);
+        }
+    }
+
+    #[test]
+    fn unknown_algorithms_are_rejected() {
+        let data = stamp([0; 16], Version::Content);
+        let mut data = stamp_algorithm(data, HashAlgorithm::Blake3);
+        data[VARIANT_BYTE] |= ALGORITHM_MASK;
+        let usid = USID::from_bytes(&data);
+
+        assert_eq!(usid.version(), Some(Version::Content));
+        assert_eq!(usid.algorithm(), None);
+        assert!(matches!(usid.validate(), Err(UsidError::UnsupportedAlgorithm(3))));
+        assert!(matches!(USID::from_string_checked(&usid.as_string()), Err(UsidError::UnsupportedAlgorithm(3))));
+    }
+}
@@ -0,0 +1,168 @@
+use std::collections::HashMap;
+use std::io::{self, Read, Seek};
+use std::sync::Mutex;
+
+use zip::ZipArchive;
+
+use crate::error::{Result, UsidError};
+
+use super::ignore::IGNORE_FILE;
+use super::{locate_root, text, IgnoreRules};
+
+/// A seekable stream an archive can be read from.
+pub(crate) trait ReadSeek: Read + Seek + Send {}
+
+impl<T: Read + Seek + Send> ReadSeek for T {}
+
+/// The files of a voicebank inside a ZIP-based archive (`.zip`, `.uar`, `.vogen`).
+pub(crate) struct ArchiveSource {
+    archive: Mutex<ZipArchive<Box<dyn ReadSeek>>>,
+    /// Maps paths relative to the voicebank root to archive entry indices.
+    entries: HashMap<String, usize>,
+}
+
+impl ArchiveSource {
+    /// Opens an archive and locates the voicebank root inside it. Returns the
+    /// source, the root's path inside the archive and the relative paths of its files.
+    pub fn open(reader: Box<dyn ReadSeek>) -> Result<(Self, String, Vec<String>)> {
+        let mut archive = ZipArchive::new(reader).map_err(invalid)?;
+
+        // Apply the built-in rules before locating the root, so that e.g. a
+        // `__MACOSX` folder next to the voicebank folder is not mistaken for content
+        let defaults = IgnoreRules::defaults();
+        let mut names = Vec::new();
+        for index in 0..archive.len() {
+            let entry = archive.by_index_raw(index).map_err(invalid)?;
+            let name = decode_name(entry.name_raw());
+            if !entry.is_dir() && !defaults.is_ignored(&name, false) {
+                names.push((name, index));
+            }
+        }
+
+        let root = locate_root(names.iter().map(|(name, _)| name.as_str()));
+        let mut source = Self {
+            archive: Mutex::new(archive),
+            entries: names.into_iter()
+                .filter_map(|(name, index)| Some((name.strip_prefix(&root)?.to_string(), index)))
+                .collect(),
+        };
+
+        if source.entries.contains_key(IGNORE_FILE) {
+            let contents = source.with_reader(IGNORE_FILE, |reader| {
+                let mut contents = Vec::new();
+                reader.read_to_end(&mut contents)?;
+                Ok(contents)
+            })?;
+
+            let rules = IgnoreRules::with_ignore_file(Some(&contents));
+            source.entries.retain(|relative, _| !rules.is_ignored(relative, false));
+        }
+
+        let mut paths = source.entries.keys().cloned().collect::<Vec<_>>();
+        paths.sort();
+
+        let root = root.trim_end_matches('/').to_string();
+        Ok((source, root, paths))
+    }
+
+    /// Streams the contents of a file to `f`. Reads from the archive are
+    /// serialised, so the archive stays locked until `f` returns.
+    pub fn with_reader<T>(&self, relative: &str, f: impl FnOnce(&mut dyn Read) -> io::Result<T>) -> Result<T> {
+        let Some(&index) = self.entries.get(relative) else {
+            return Err(UsidError::InvalidArchive(format!("{} not found in archive", relative)));
+        };
+
+        let mut archive = self.archive.lock().unwrap_or_else(|e| e.into_inner());
+        let mut entry = archive.by_index(index).map_err(invalid)?;
+        f(&mut entry).map_err(|e| UsidError::InvalidArchive(format!("Failed to read {}: {}", relative, e)))
+    }
+}
+
+fn invalid(e: zip::result::ZipError) -> UsidError {
+    UsidError::InvalidArchive(e.to_string())
+}
+
+/// Decodes an entry name. Archives created on Japanese Windows store names in
+/// Shift-JIS without flagging them, so names are decoded like voicebank text files.
+fn decode_name(raw: &[u8]) -> String {
+    text::normalize_path(&text::decode(raw))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::io::{Cursor, Write};
+    use std::path::Path;
+
+    use zip::write::SimpleFileOptions;
+    use zip::{CompressionMethod, ZipWriter};
+
+    use crate::USID;
+
+    /// Stands in for the Shift-JIS folder name `あ` (`82 a0`) while the archive is written.
+    const PLACEHOLDER: &[u8] = b"@@/";
+    const SHIFT_JIS: &[u8] = b"\x82\xa0/";
+
+    /// A UTAU voicebank with a Japanese subfolder and a draft folder it ignores.
+    const FILES: &[(&str, &str)] = &[
+        ("character.txt", "name=Teto\n"),
+        ("oto.ini", "a.wav=a,0,0,0,0,0\n"),
+        ("@@/oto.ini", "i.wav=i,0,0,0,0,0\n"),
+        (".usidignore", "draft/\n"),
+        ("draft/oto.ini", "u.wav=u,0,0,0,0,0\n"),
+    ];
+
+    fn write_tree(root: &Path, skip_ignored: bool) {
+        for (name, contents) in FILES {
+            if skip_ignored && (name.starts_with("draft/") || *name == ".usidignore") {
+                continue;
+            }
+
+            let path = root.join(name.replace("@@", "あ"));
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, contents).unwrap();
+        }
+    }
+
+    /// Zips the voicebank inside two wrapper folders, as created by macOS Finder
+    /// on Japanese Windows: with a `__MACOSX` folder and Shift-JIS names.
+    fn archive() -> Vec<u8> {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+
+        for (name, contents) in FILES {
+            zip.start_file(format!("Wrapper/Teto/{}", name), options).unwrap();
+            zip.write_all(contents.as_bytes()).unwrap();
+        }
+        zip.start_file("__MACOSX/Wrapper/Teto/._oto.ini", options).unwrap();
+        zip.write_all(b"resource fork").unwrap();
+
+        let mut bytes = zip.finish().unwrap().into_inner();
+
+        // The zip crate only writes UTF-8 names, so swap in the Shift-JIS bytes
+        for i in 0..bytes.len() - PLACEHOLDER.len() {
+            if &bytes[i..i + PLACEHOLDER.len()] == PLACEHOLDER {
+                bytes[i..i + SHIFT_JIS.len()].copy_from_slice(SHIFT_JIS);
+            }
+        }
+        bytes
+    }
+
+    #[test]
+    fn archive_matches_extracted_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        write_tree(dir.path(), false);
+
+        let from_archive = USID::from_archive(Cursor::new(archive())).unwrap();
+        assert_eq!(from_archive, USID::from_voicebank(dir.path()).unwrap());
+    }
+
+    #[test]
+    fn archive_leaves_out_ignored_and_junk_files() {
+        let clean = tempfile::tempdir().unwrap();
+        write_tree(clean.path(), true);
+
+        let from_archive = USID::from_archive(Cursor::new(archive())).unwrap();
+        assert_eq!(from_archive, USID::from_voicebank(clean.path()).unwrap());
+    }
+}
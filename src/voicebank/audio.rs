@@ -0,0 +1,83 @@
+use std::io::{BufReader, Read};
+
+use hound::{SampleFormat, WavReader};
+
+/// Prefix of fingerprints, so they never collide with raw file bytes.
+const ENVELOPE_MAGIC: &[u8] = b"usid:envelope";
+/// Length of an envelope window in milliseconds.
+const WINDOW_MS: u64 = 10;
+/// Quietest level represented in the envelope; anything quieter is silence.
+const FLOOR_DB: f32 = -96.0;
+
+/// Fingerprints a WAV sample by its loudness envelope: the RMS level of every
+/// 10 ms window, quantised to whole decibels. Working on windows of fixed
+/// duration and on samples scaled to `[-1, 1]` makes the fingerprint independent
+/// of bit depth and largely of sample rate. The sample is decoded as it is read,
+/// so memory use does not grow with its length. Returns `None` if the sample
+/// cannot be decoded.
+pub(crate) fn fingerprint(reader: impl Read) -> Option<Vec<u8>> {
+    let mut reader = WavReader::new(BufReader::new(reader)).ok()?;
+    let spec = reader.spec();
+
+    // Windows span all channels, so the level is the mean power across channels
+    let window = (u64::from(spec.sample_rate).checked_mul(WINDOW_MS)? / 1000)
+        .max(1)
+        .checked_mul(u64::from(spec.channels))
+        .and_then(|w| usize::try_from(w).ok())
+        .filter(|&w| w > 0)?;
+
+    let mut envelope = Envelope::new(window);
+    match spec.sample_format {
+        SampleFormat::Float => {
+            for sample in reader.samples::<f32>() {
+                envelope.push(sample.ok()?);
+            }
+        }
+        SampleFormat::Int => {
+            let scale = 1.0 / 1u64.checked_shl(u32::from(spec.bits_per_sample).checked_sub(1)?)? as f32;
+            for sample in reader.samples::<i32>() {
+                envelope.push(sample.ok()? as f32 * scale);
+            }
+        }
+    }
+
+    Some(envelope.finish())
+}
+
+/// Accumulates the power of each window as samples arrive.
+struct Envelope {
+    levels: Vec<u8>,
+    window: usize,
+    power: f32,
+    len: usize,
+}
+
+impl Envelope {
+    fn new(window: usize) -> Self {
+        Self { levels: ENVELOPE_MAGIC.to_vec(), window, power: 0.0, len: 0 }
+    }
+
+    fn push(&mut self, sample: f32) {
+        self.power += sample * sample;
+        self.len += 1;
+        if self.len == self.window {
+            self.end_window();
+        }
+    }
+
+    fn end_window(&mut self) {
+        let power = self.power / self.len as f32;
+        let db = (10.0 * power.log10()).clamp(FLOOR_DB, 0.0);
+        self.levels.push((db - FLOOR_DB).round() as u8);
+        self.power = 0.0;
+        self.len = 0;
+    }
+
+    /// Ends the last, possibly shorter, window and returns the fingerprint.
+    fn finish(mut self) -> Vec<u8> {
+        if self.len > 0 {
+            self.end_window();
+        }
+        self.levels
+    }
+}
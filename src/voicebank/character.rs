@@ -0,0 +1,208 @@
+use crate::error::Result;
+use crate::manifest::Normalization;
+
+use super::{canonicalize_image, file_name, has_image_extension, is_root, text, yaml, VoicebankFiles};
+
+/// `character.yaml` keys that reference images.
+const YAML_IMAGE_KEYS: &[&str] = &["image", "portrait", "icon"];
+
+/// `character.txt` keys that reference images.
+const TXT_IMAGE_KEYS: &[&str] = &["image"];
+
+/// Lists the character metadata shared by every voicebank format:
+/// `character.txt`, `character.yaml`, `readme.txt` and the images they reference.
+pub(crate) fn identity_files(files: &VoicebankFiles) -> Result<Vec<String>> {
+    let mut identity = files.paths()
+        .filter(|p| is_root(p) && matches!(file_name(p).as_str(), "character.txt" | "character.yaml" | "readme.txt"))
+        .map(String::from)
+        .collect::<Vec<_>>();
+
+    identity.extend(image_roles(files)?.into_iter().map(|(relative, _)| relative));
+    Ok(identity)
+}
+
+/// Finds the images the character metadata references, paired with their role,
+/// e.g. `image:portrait` for the `portrait` key. Images are identified by role
+/// rather than by file name, so converting `icon.bmp` to `icon.png` and updating
+/// the reference does not change the USID. `character.yaml` takes precedence
+/// when both files name an image for the same role.
+pub(crate) fn image_roles(files: &VoicebankFiles) -> Result<Vec<(String, String)>> {
+    let mut references = Vec::new();
+
+    let root_file = |name: &str| files.paths().find(|p| is_root(p) && file_name(p) == name);
+    if let Some(relative) = root_file("character.yaml")
+        && let Some(value) = yaml::parse(&files.read(relative)?)
+    {
+        for key in YAML_IMAGE_KEYS {
+            references.extend(yaml::top_level_strings(&value, &[key]).into_iter().map(|image| (*key, image)));
+        }
+    }
+    if let Some(relative) = root_file("character.txt") {
+        let contents = files.read(relative)?;
+        for key in TXT_IMAGE_KEYS {
+            references.extend(character_txt_value(&contents, key).map(|image| (*key, image)));
+        }
+    }
+
+    let mut roles = Vec::<(String, String)>::new();
+    for (key, image) in references {
+        let role = format!("image:{}", key);
+        let Some(relative) = files.find(&image).filter(|p| has_image_extension(p)) else {
+            continue;
+        };
+
+        if roles.iter().all(|(r, existing)| r != relative && *existing != role) {
+            roles.push((relative.to_string(), role));
+        }
+    }
+
+    Ok(roles)
+}
+
+/// Returns a top-level `character.yaml` or `character.txt` value such as the
+/// voicebank's name. `character.yaml` takes precedence, as OpenUtau does.
+pub(crate) fn metadata(files: &VoicebankFiles, key: &str) -> Option<String> {
+    let root_file = |name: &str| files.paths().find(|p| is_root(p) && file_name(p) == name);
+
+    let from_yaml = root_file("character.yaml")
+        .and_then(|relative| yaml::parse(&files.read(relative).ok()?))
+        .and_then(|value| yaml::top_level_strings(&value, &[key]).into_iter().next());
+
+    from_yaml.or_else(|| {
+        let relative = root_file("character.txt")?;
+        character_txt_value(&files.read(relative).ok()?, key)
+    })
+}
+
+/// Lists the affixes of the subbanks declared in an OpenUtau `character.yaml`.
+pub(crate) fn subbanks(files: &VoicebankFiles) -> Result<Vec<String>> {
+    let Some(relative) = files.paths().find(|p| is_root(p) && file_name(p) == "character.yaml") else {
+        return Ok(Vec::new());
+    };
+
+    let Some(value) = yaml::parse(&files.read(relative)?) else {
+        return Ok(Vec::new());
+    };
+
+    let Some(subbanks) = yaml::get(&value, "subbanks").and_then(|s| s.as_vec()) else {
+        return Ok(Vec::new());
+    };
+
+    Ok(subbanks.iter()
+        .map(|subbank| {
+            let affix = |key: &str| yaml::get(subbank, key).and_then(|v| v.as_str()).unwrap_or_default();
+            format!("{}{}", affix("prefix"), affix("suffix"))
+        })
+        .collect())
+}
+
+/// Canonicalises a character metadata file or image, or returns `None` if
+/// `relative` is neither.
+pub(crate) fn canonicalize(relative: &str, contents: &[u8]) -> Option<Vec<u8>> {
+    if let Some(canonical) = canonicalize_image(relative, contents) {
+        return Some(canonical);
+    }
+
+    if !is_root(relative) {
+        return None;
+    }
+
+    let canonical = match file_name(relative).as_str() {
+        // Image references are left out, as the images are hashed under their role
+        "character.txt" => {
+            // Sort after canonicalising, so key case and spacing cannot affect the order
+            let text = text::decode(contents);
+            let mut lines = text::lines(&text)
+                .filter(|line| !is_image_reference(line))
+                .map(canonical_key_value)
+                .collect::<Vec<_>>();
+            lines.sort_unstable();
+            lines.join("\n")
+        }
+        "character.yaml" => yaml::canonical_without(contents, YAML_IMAGE_KEYS),
+        "readme.txt" => text::normalize(contents),
+        _ => return None,
+    };
+
+    Some(canonical.into_bytes())
+}
+
+/// Describes the normalisation of a character metadata file or image, or returns
+/// `None` if `relative` is neither.
+pub(crate) fn normalization(relative: &str) -> Option<Normalization> {
+    if has_image_extension(relative) {
+        return Some(Normalization::Image);
+    }
+
+    if !is_root(relative) {
+        return None;
+    }
+
+    match file_name(relative).as_str() {
+        "character.txt" => Some(Normalization::KeyValue),
+        "character.yaml" => Some(Normalization::Yaml),
+        "readme.txt" => Some(Normalization::Text),
+        _ => None,
+    }
+}
+
+fn canonical_key_value(line: &str) -> String {
+    match line.split_once('=') {
+        Some((key, value)) => format!("{}={}", key.trim().to_lowercase(), value.trim()),
+        None => line.to_string(),
+    }
+}
+
+fn is_image_reference(line: &str) -> bool {
+    line.split_once('=').is_some_and(|(key, _)| {
+        TXT_IMAGE_KEYS.iter().any(|k| key.trim().eq_ignore_ascii_case(k))
+    })
+}
+
+/// Returns the value of a `key=value` line of a `character.txt`.
+fn character_txt_value(bytes: &[u8], key: &str) -> Option<String> {
+    let text = text::decode(bytes);
+    text::lines(&text)
+        .filter_map(|line| line.split_once('='))
+        .find(|(k, _)| k.trim().eq_ignore_ascii_case(key))
+        .map(|(_, value)| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn character_txt_ignores_key_case_spacing_and_order() {
+        let a = canonicalize("character.txt", b"name=Teto\nauthor=me\n");
+        let b = canonicalize("character.txt", b"Author = me\r\nNAME=Teto\r\n");
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn character_txt_leaves_out_image_references() {
+        let a = canonicalize("character.txt", b"name=Teto\nimage=icon.bmp\n");
+        let b = canonicalize("character.txt", b"name=Teto\nImage = portrait.png\n");
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn character_yaml_leaves_out_image_references() {
+        let a = canonicalize("character.yaml", b"name: Teto\nportrait: portrait.bmp\n");
+        let b = canonicalize("character.yaml", b"portrait: art/portrait.png\nname: Teto\n");
+        assert_eq!(a, b);
+        assert_ne!(a, canonicalize("character.yaml", b"name: Ted\n"));
+    }
+
+    #[test]
+    fn only_root_metadata_is_canonicalised() {
+        assert_eq!(canonicalize("A3/character.txt", b"name=Teto"), None);
+    }
+
+    #[test]
+    fn character_txt_sorts_by_canonical_key() {
+        let canonical = canonicalize("character.txt", b"Name=Teto\nauthor=me\n").unwrap();
+        assert_eq!(canonical, b"author=me\nname=Teto");
+    }
+}
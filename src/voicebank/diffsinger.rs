@@ -0,0 +1,77 @@
+use crate::error::Result;
+use crate::manifest::Normalization;
+
+use super::{character, extension, file_name, is_root, text, yaml, VoicebankFiles, VoicebankFormat};
+
+/// Model and embedding files, hashed byte for byte.
+const MODEL_EXTENSIONS: &[&str] = &["onnx", "emb"];
+
+/// DiffSinger voicebanks. Identity is defined by the character metadata, every
+/// `dsconfig.yaml` (including those of the duration, pitch and variance models),
+/// the phoneme dictionaries and inventories and the ONNX models.
+pub struct DiffSingerFormat;
+
+impl DiffSingerFormat {
+    fn is_config(relative: &str) -> bool {
+        let name = file_name(relative);
+        name == "dsconfig.yaml" || (name.starts_with("dsdict") && extension(relative) == "yaml")
+    }
+
+    fn is_phoneme_list(relative: &str) -> bool {
+        let extension = extension(relative);
+        (file_name(relative).starts_with("phonemes") && extension == "txt") || extension == "json"
+    }
+}
+
+impl VoicebankFormat for DiffSingerFormat {
+    fn name(&self) -> &str {
+        "diffsinger"
+    }
+
+    fn detect(&self, files: &VoicebankFiles) -> f32 {
+        // DiffSinger banks usually ship a character.txt too, so outrank UTAU
+        if files.paths().any(|p| is_root(p) && file_name(p) == "dsconfig.yaml") { 0.9 } else { 0.0 }
+    }
+
+    fn identity_files(&self, files: &VoicebankFiles) -> Result<Vec<String>> {
+        let mut identity = character::identity_files(files)?;
+        identity.extend(
+            files.paths()
+                .filter(|p| {
+                    Self::is_config(p)
+                        || Self::is_phoneme_list(p)
+                        || MODEL_EXTENSIONS.contains(&extension(p).as_str())
+                })
+                .map(String::from),
+        );
+        Ok(identity)
+    }
+
+    fn canonicalize(&self, relative: &str, contents: Vec<u8>) -> Vec<u8> {
+        if let Some(canonical) = character::canonicalize(relative, &contents) {
+            canonical
+        } else if Self::is_config(relative) {
+            yaml::canonical(&contents).into_bytes()
+        } else if Self::is_phoneme_list(relative) {
+            text::normalize(&contents).into_bytes()
+        } else {
+            contents
+        }
+    }
+
+    fn normalization(&self, relative: &str) -> Normalization {
+        if let Some(normalization) = character::normalization(relative) {
+            normalization
+        } else if Self::is_config(relative) {
+            Normalization::Yaml
+        } else if Self::is_phoneme_list(relative) {
+            Normalization::Text
+        } else {
+            Normalization::None
+        }
+    }
+
+    fn streams(&self, relative: &str) -> bool {
+        self.normalization(relative) == Normalization::None
+    }
+}
@@ -0,0 +1,65 @@
+use crate::error::Result;
+use crate::manifest::Normalization;
+
+use super::{character, extension, file_name, is_root, text, yaml, VoicebankFiles, VoicebankFormat};
+
+/// Model weights and normalisation statistics, hashed byte for byte.
+const MODEL_EXTENSIONS: &[&str] = &["pth", "onnx", "npy", "ckpt"];
+
+/// ENUNU/NNSVS voicebanks. Identity is defined by the character metadata,
+/// `enuconfig.yaml` and the model configs, the phoneme table and question files,
+/// and the model weights.
+pub struct EnunuFormat;
+
+impl VoicebankFormat for EnunuFormat {
+    fn name(&self) -> &str {
+        "enunu"
+    }
+
+    fn detect(&self, files: &VoicebankFiles) -> f32 {
+        // ENUNU banks usually ship a character.txt too, so outrank UTAU
+        if files.paths().any(|p| is_root(p) && file_name(p) == "enuconfig.yaml") { 0.9 } else { 0.0 }
+    }
+
+    fn identity_files(&self, files: &VoicebankFiles) -> Result<Vec<String>> {
+        let mut identity = character::identity_files(files)?;
+        identity.extend(
+            files.paths()
+                .filter(|p| {
+                    let extension = extension(p);
+                    matches!(extension.as_str(), "yaml" | "yml" | "table" | "hed")
+                        || MODEL_EXTENSIONS.contains(&extension.as_str())
+                })
+                .map(String::from),
+        );
+        Ok(identity)
+    }
+
+    fn canonicalize(&self, relative: &str, contents: Vec<u8>) -> Vec<u8> {
+        if let Some(canonical) = character::canonicalize(relative, &contents) {
+            return canonical;
+        }
+
+        match extension(relative).as_str() {
+            "yaml" | "yml" => yaml::canonical(&contents).into_bytes(),
+            "table" | "hed" => text::normalize(&contents).into_bytes(),
+            _ => contents,
+        }
+    }
+
+    fn normalization(&self, relative: &str) -> Normalization {
+        if let Some(normalization) = character::normalization(relative) {
+            return normalization;
+        }
+
+        match extension(relative).as_str() {
+            "yaml" | "yml" => Normalization::Yaml,
+            "table" | "hed" => Normalization::Text,
+            _ => Normalization::None,
+        }
+    }
+
+    fn streams(&self, relative: &str) -> bool {
+        self.normalization(relative) == Normalization::None
+    }
+}
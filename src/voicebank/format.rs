@@ -0,0 +1,180 @@
+use crate::error::Result;
+use crate::manifest::Normalization;
+
+use super::{canonicalize_image, file_name, has_image_extension, is_root, DiffSingerFormat, EnunuFormat, UtauFormat, VoicebankFiles};
+
+/// A voicebank layout that knows which of its files define a voicebank's identity.
+///
+/// Implement this to teach USID about an in-house format, then register it in
+/// [`HashOptions::formats`](crate::HashOptions::formats) and pass the options to
+/// [`USID::from_voicebank_with`](crate::USID::from_voicebank_with).
+pub trait VoicebankFormat: Send + Sync {
+    /// Short identifier of the format, e.g. `"utau"`.
+    fn name(&self) -> &str;
+
+    /// How confident the format is that `files` form one of its voicebanks, from
+    /// `0.0` (not at all) to `1.0` (certain).
+    fn detect(&self, files: &VoicebankFiles) -> f32;
+
+    /// Lists the identity-defining files, as relative paths from `files`.
+    fn identity_files(&self, files: &VoicebankFiles) -> Result<Vec<String>>;
+
+    /// Normalises the contents of an identity file so that insignificant edits,
+    /// such as a different encoding or line endings, do not change the USID.
+    fn canonicalize(&self, _relative: &str, contents: Vec<u8>) -> Vec<u8> {
+        contents
+    }
+
+    /// Describes what [`VoicebankFormat::canonicalize`] does to an identity file,
+    /// for the [`UsidManifest`](crate::UsidManifest).
+    fn normalization(&self, _relative: &str) -> Normalization {
+        Normalization::None
+    }
+
+    /// Reports whether an identity file is hashed byte for byte, so it can be
+    /// streamed straight into the hasher instead of being read into memory. Such
+    /// files are never passed to [`VoicebankFormat::canonicalize`]. Opt in for
+    /// large files such as models; by default every file is canonicalised.
+    fn streams(&self, _relative: &str) -> bool {
+        false
+    }
+}
+
+/// The set of formats considered when detecting a voicebank's layout.
+pub struct FormatRegistry {
+    formats: Vec<Box<dyn VoicebankFormat>>,
+}
+
+impl FormatRegistry {
+    /// Creates a registry without any formats.
+    pub fn new() -> Self {
+        Self { formats: Vec::new() }
+    }
+
+    pub fn register(&mut self, format: impl VoicebankFormat + 'static) -> &mut Self {
+        self.formats.push(Box::new(format));
+        self
+    }
+
+    pub fn formats(&self) -> impl Iterator<Item = &dyn VoicebankFormat> {
+        self.formats.iter().map(|f| f.as_ref())
+    }
+
+    /// Returns the format most confident that it matches `files`. Ties go to the
+    /// format registered first.
+    pub fn detect(&self, files: &VoicebankFiles) -> Option<&dyn VoicebankFormat> {
+        let mut best: Option<(f32, &dyn VoicebankFormat)> = None;
+        for format in self.formats() {
+            let confidence = format.detect(files);
+            if confidence > 0.0 && best.is_none_or(|(c, _)| confidence > c) {
+                best = Some((confidence, format));
+            }
+        }
+
+        best.map(|(_, format)| format)
+    }
+}
+
+impl Default for FormatRegistry {
+    /// Creates a registry with every built-in format.
+    fn default() -> Self {
+        let mut registry = Self::new();
+        registry
+            .register(DiffSingerFormat)
+            .register(EnunuFormat)
+            .register(UtauFormat)
+            .register(GenericFormat);
+        registry
+    }
+}
+
+/// Configuration and metadata files that define the identity of a voicebank
+/// in an unrecognised layout. Matched case-insensitively anywhere in the tree.
+const GENERIC_IDENTITY_FILES: &[&str] = &[
+    "character.txt",
+    "character.yaml",
+    "oto.ini",
+    "prefix.map",
+    "readme.txt",
+];
+
+/// Fallback for unrecognised layouts: known metadata files anywhere in the tree,
+/// hashed byte for byte, and images in the root.
+pub struct GenericFormat;
+
+impl GenericFormat {
+    fn is_identity(relative: &str) -> bool {
+        GENERIC_IDENTITY_FILES.contains(&file_name(relative).as_str())
+            || (is_root(relative) && has_image_extension(relative))
+    }
+}
+
+impl VoicebankFormat for GenericFormat {
+    fn name(&self) -> &str {
+        "generic"
+    }
+
+    fn detect(&self, files: &VoicebankFiles) -> f32 {
+        if files.paths().any(Self::is_identity) { 0.01 } else { 0.0 }
+    }
+
+    fn identity_files(&self, files: &VoicebankFiles) -> Result<Vec<String>> {
+        Ok(files.paths().filter(|p| Self::is_identity(p)).map(String::from).collect())
+    }
+
+    fn canonicalize(&self, relative: &str, contents: Vec<u8>) -> Vec<u8> {
+        canonicalize_image(relative, &contents).unwrap_or(contents)
+    }
+
+    fn normalization(&self, relative: &str) -> Normalization {
+        if has_image_extension(relative) { Normalization::Image } else { Normalization::None }
+    }
+
+    fn streams(&self, relative: &str) -> bool {
+        self.normalization(relative) == Normalization::None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::{HashOptions, USID};
+
+    /// A format that only overrides `canonicalize`, like a minimal in-house one.
+    struct CaseInsensitiveFormat;
+
+    impl VoicebankFormat for CaseInsensitiveFormat {
+        fn name(&self) -> &str {
+            "case-insensitive"
+        }
+
+        fn detect(&self, _files: &VoicebankFiles) -> f32 {
+            1.0
+        }
+
+        fn identity_files(&self, files: &VoicebankFiles) -> Result<Vec<String>> {
+            Ok(files.paths().map(String::from).collect())
+        }
+
+        fn canonicalize(&self, _relative: &str, contents: Vec<u8>) -> Vec<u8> {
+            contents.to_ascii_lowercase()
+        }
+    }
+
+    fn usid(contents: &str) -> USID {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("voice.cfg"), contents).unwrap();
+
+        let mut options = HashOptions { formats: FormatRegistry::new(), ..Default::default() };
+        options.formats.register(CaseInsensitiveFormat);
+        USID::from_voicebank_with(dir.path(), &options).unwrap()
+    }
+
+    #[test]
+    fn custom_canonicalize_is_applied_by_default() {
+        assert_eq!(usid("Name=Teto"), usid("name=teto"));
+        assert_ne!(usid("name=teto"), usid("name=ted"));
+    }
+}
@@ -0,0 +1,109 @@
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+
+use super::text;
+
+/// Name of the file in a voicebank root that lists additional paths to ignore,
+/// in gitignore syntax.
+pub(crate) const IGNORE_FILE: &str = ".usidignore";
+
+/// Files that never affect a voicebank's identity: resampler caches written by
+/// UTAU and OpenUtau, and junk left behind by macOS and Windows.
+const DEFAULT_RULES: &[&str] = &[
+    // Frequency tables, including `*_wav.frq`
+    "*.frq",
+    // Moresampler caches, including `desc.mrq`
+    "*.llsm",
+    "*.mrq",
+    ".DS_Store",
+    "._*",
+    "Thumbs.db",
+    "__MACOSX/",
+];
+
+/// Gitignore-style rules deciding which files are left out of a voicebank's listing.
+pub(crate) struct IgnoreRules {
+    matcher: Gitignore,
+}
+
+impl IgnoreRules {
+    /// The built-in rules only.
+    pub fn defaults() -> Self {
+        Self::with_ignore_file(None)
+    }
+
+    /// The built-in rules plus those of a `.usidignore` file.
+    pub fn with_ignore_file(contents: Option<&[u8]>) -> Self {
+        let mut builder = GitignoreBuilder::new("");
+        let custom = contents.map(text::decode).unwrap_or_default();
+
+        for line in DEFAULT_RULES.iter().copied().chain(custom.lines()) {
+            // Invalid patterns are skipped rather than failing the whole voicebank
+            let _ = builder.add_line(None, line);
+        }
+
+        let matcher = builder.build().unwrap_or_else(|_| Gitignore::empty());
+        Self { matcher }
+    }
+
+    /// Reports whether the file or folder at `relative`, or any folder containing
+    /// it, is ignored.
+    pub fn is_ignored(&self, relative: &str, is_dir: bool) -> bool {
+        self.matcher.matched_path_or_any_parents(relative, is_dir).is_ignore()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::voicebank::VoicebankFiles;
+
+    #[test]
+    fn defaults_leave_out_caches_and_junk() {
+        let rules = IgnoreRules::defaults();
+        for path in ["a_wav.frq", "A3/ka_wav.frq", "desc.mrq", "a.llsm", "._oto.ini", "A3/.DS_Store", "Thumbs.db"] {
+            assert!(rules.is_ignored(path, false), "{} should be ignored", path);
+        }
+        assert!(rules.is_ignored("__MACOSX", true));
+        assert!(rules.is_ignored("__MACOSX/A3/._oto.ini", false));
+
+        for path in ["a.wav", "oto.ini", "A3/oto.ini", "character.txt", "frq/readme.txt"] {
+            assert!(!rules.is_ignored(path, false), "{} should not be ignored", path);
+        }
+    }
+
+    #[test]
+    fn ignore_file_adds_patterns() {
+        let rules = IgnoreRules::with_ignore_file(Some(b"*.bak\ndrafts/\n/notes.txt\n!keep.bak\n"));
+        assert!(rules.is_ignored("oto.ini.bak", false));
+        assert!(rules.is_ignored("A3/oto.ini.bak", false));
+        assert!(!rules.is_ignored("keep.bak", false));
+        assert!(rules.is_ignored("notes.txt", false));
+        assert!(!rules.is_ignored("A3/notes.txt", false));
+        assert!(rules.is_ignored("a_wav.frq", false));
+    }
+
+    #[test]
+    fn directory_patterns_leave_out_everything_below() {
+        let rules = IgnoreRules::with_ignore_file(Some(b"drafts/\n"));
+        assert!(rules.is_ignored("drafts", true));
+        assert!(rules.is_ignored("drafts/oto.ini", false));
+        assert!(rules.is_ignored("A3/drafts/old/oto.ini", false));
+        assert!(!rules.is_ignored("drafts", false));
+    }
+
+    #[test]
+    fn listing_applies_the_rules() {
+        let dir = tempfile::tempdir().unwrap();
+        for relative in ["oto.ini", "a_wav.frq", "desc.mrq", "._oto.ini", "__MACOSX/oto.ini", "drafts/oto.ini", "A3/oto.ini"] {
+            let path = dir.path().join(relative);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, relative).unwrap();
+        }
+        fs::write(dir.path().join(IGNORE_FILE), "drafts/\n").unwrap();
+
+        let files = VoicebankFiles::from_dir(dir.path()).unwrap();
+        assert_eq!(files.paths().collect::<Vec<_>>(), [IGNORE_FILE, "A3/oto.ini", "oto.ini"]);
+    }
+}
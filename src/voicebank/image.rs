@@ -0,0 +1,97 @@
+use image::imageops::FilterType;
+use image::{DynamicImage, RgbaImage};
+
+/// Prefix of canonical image contents, so decoded pixels never collide with raw file bytes.
+const PIXELS_MAGIC: &[u8] = b"usid:rgba8";
+
+/// Maximum Hamming distance between two perceptual hashes for the images to be
+/// considered a near match.
+pub(crate) const NEAR_MATCH_DISTANCE: u32 = 5;
+
+/// Decodes an image to 8-bit RGBA pixels, so that converting a portrait between
+/// formats (e.g. BMP to PNG) or re-compressing it losslessly keeps its identity.
+/// The colour of fully transparent pixels is discarded. Returns `None` if the
+/// image cannot be decoded.
+pub(crate) fn canonicalize(contents: &[u8]) -> Option<Vec<u8>> {
+    let mut pixels = decode(contents)?;
+    for pixel in pixels.pixels_mut() {
+        if pixel.0[3] == 0 {
+            pixel.0 = [0; 4];
+        }
+    }
+
+    let mut canonical = Vec::with_capacity(PIXELS_MAGIC.len() + 8 + pixels.len());
+    canonical.extend_from_slice(PIXELS_MAGIC);
+    canonical.extend_from_slice(&pixels.width().to_le_bytes());
+    canonical.extend_from_slice(&pixels.height().to_le_bytes());
+    canonical.extend_from_slice(pixels.as_raw());
+    Some(canonical)
+}
+
+/// Computes a 64-bit difference hash (dHash) of an image. Visually similar
+/// images, e.g. a portrait saved as a lossy JPEG, have hashes a few bits apart.
+pub(crate) fn perceptual_hash(contents: &[u8]) -> Option<u64> {
+    let pixels = decode(contents)?;
+
+    // Composite onto white so transparent regions compare equal regardless of colour
+    let mut flattened = RgbaImage::from_pixel(pixels.width(), pixels.height(), image::Rgba([255; 4]));
+    image::imageops::overlay(&mut flattened, &pixels, 0, 0);
+
+    let small = DynamicImage::ImageRgba8(flattened)
+        .resize_exact(9, 8, FilterType::Triangle)
+        .into_luma8();
+
+    let mut hash = 0u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            let brighter = small.get_pixel(x, y).0[0] > small.get_pixel(x + 1, y).0[0];
+            hash = (hash << 1) | brighter as u64;
+        }
+    }
+    Some(hash)
+}
+
+fn decode(contents: &[u8]) -> Option<RgbaImage> {
+    image::load_from_memory(contents).ok().map(|image| image.into_rgba8())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use image::{ImageFormat, Rgb, RgbImage};
+
+    use super::*;
+
+    fn encode(image: &RgbImage, format: ImageFormat) -> Vec<u8> {
+        let mut bytes = Cursor::new(Vec::new());
+        image.write_to(&mut bytes, format).unwrap();
+        bytes.into_inner()
+    }
+
+    fn gradient() -> RgbImage {
+        RgbImage::from_fn(16, 16, |x, y| Rgb([x as u8 * 16, y as u8 * 16, 128]))
+    }
+
+    #[test]
+    fn bmp_and_png_of_the_same_pixels_are_equal() {
+        let bmp = encode(&gradient(), ImageFormat::Bmp);
+        let png = encode(&gradient(), ImageFormat::Png);
+        assert_ne!(bmp, png);
+        assert_eq!(canonicalize(&bmp), canonicalize(&png));
+    }
+
+    #[test]
+    fn different_pixels_differ() {
+        let mut changed = gradient();
+        changed.put_pixel(0, 0, Rgb([255, 0, 0]));
+        let a = encode(&gradient(), ImageFormat::Png);
+        let b = encode(&changed, ImageFormat::Png);
+        assert_ne!(canonicalize(&a), canonicalize(&b));
+    }
+
+    #[test]
+    fn undecodable_images_are_not_canonicalised() {
+        assert_eq!(canonicalize(b"BMfake"), None);
+    }
+}
@@ -0,0 +1,490 @@
+use std::fs::{self, File};
+use std::io::{self, Read};
+#[cfg(feature = "archive")]
+use std::io::Seek;
+use std::path::{Path, PathBuf};
+
+use rayon::prelude::*;
+
+use crate::cache::FileStamp;
+use crate::digest::UsidDigest;
+use crate::error::{Result, UsidError};
+use crate::hash::{HashAlgorithm, Hasher, HashingReader};
+use crate::lineage;
+use crate::manifest::{ManifestEntry, Normalization, UsidManifest, ALGORITHM_VERSION};
+use crate::options::HashOptions;
+
+#[cfg(feature = "archive")]
+mod archive;
+mod audio;
+mod character;
+mod diffsinger;
+mod enunu;
+mod format;
+mod ignore;
+mod image;
+mod text;
+mod utau;
+mod yaml;
+
+pub use diffsinger::DiffSingerFormat;
+pub use enunu::EnunuFormat;
+pub use format::{FormatRegistry, GenericFormat, VoicebankFormat};
+pub use utau::UtauFormat;
+
+pub(crate) use ignore::IgnoreRules;
+pub(crate) use image::NEAR_MATCH_DISTANCE;
+
+/// Image types considered for portraits and icons.
+const IMAGE_EXTENSIONS: &[&str] = &["bmp", "png", "jpg", "jpeg", "gif"];
+
+/// Domain separator so voicebank hashes never collide with other uses of the hasher.
+const DOMAIN: &[u8] = b"usid:voicebank";
+
+/// The files of a voicebank, as `/`-separated paths relative to its root.
+pub struct VoicebankFiles {
+    root: PathBuf,
+    source: Source,
+    paths: Vec<String>,
+}
+
+/// Where the contents of voicebank files are read from.
+enum Source {
+    Dir,
+    #[cfg(feature = "archive")]
+    Archive(archive::ArchiveSource),
+}
+
+impl VoicebankFiles {
+    /// Lists every file below `root`, sorted by relative path. Resampler caches,
+    /// OS junk and anything matched by a `.usidignore` file in `root` are left out.
+    ///
+    /// If `root` contains nothing but a single folder, that folder is taken as the
+    /// voicebank root instead, repeatedly, the same way the root of an archive is
+    /// found. A voicebank therefore has the same USID whether it is extracted
+    /// into a folder of its own or not.
+    pub fn from_dir(root: impl AsRef<Path>) -> Result<Self> {
+        let root = root.as_ref();
+        if !root.is_dir() {
+            return Err(UsidError::NotADirectory(root.to_path_buf()));
+        }
+
+        let ignore_file = root.join(ignore::IGNORE_FILE);
+        let rules = match fs::read(&ignore_file) {
+            Ok(contents) => IgnoreRules::with_ignore_file(Some(&contents)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => IgnoreRules::defaults(),
+            Err(e) => return Err(UsidError::io(ignore_file, e)),
+        };
+
+        let mut paths = Vec::new();
+        walk(root, root, &rules, &mut paths)?;
+
+        let wrapped = locate_root(paths.iter().map(String::as_str));
+        if !wrapped.is_empty() {
+            return Self::from_dir(root.join(wrapped.trim_end_matches('/')));
+        }
+
+        // Directory iteration order is platform dependent, so sort for determinism
+        paths.sort();
+        Ok(Self { root: root.to_path_buf(), source: Source::Dir, paths })
+    }
+
+    /// Lists the files of a voicebank inside a ZIP-based archive (`.zip`, `.uar`,
+    /// `.vogen`) without extracting it. The voicebank root is found by descending
+    /// through folders that wrap nothing but a single subfolder, and the same
+    /// ignore rules as [`VoicebankFiles::from_dir`] apply, so the listing matches
+    /// that of the extracted voicebank directory, wherever it was extracted to.
+    #[cfg(feature = "archive")]
+    pub fn from_archive(reader: impl Read + Seek + Send + 'static) -> Result<Self> {
+        let (source, root, paths) = archive::ArchiveSource::open(Box::new(reader))?;
+        Ok(Self { root: PathBuf::from(root), source: Source::Archive(source), paths })
+    }
+
+    /// Lists the voicebank at `path`, which is either a directory or, with the
+    /// `archive` feature, a ZIP-based archive file.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        if path.is_dir() {
+            return Self::from_dir(path);
+        }
+
+        #[cfg(feature = "archive")]
+        if path.is_file() {
+            let file = File::open(path).map_err(|e| UsidError::io(path, e))?;
+            return Self::from_archive(io::BufReader::new(file));
+        }
+
+        Err(UsidError::NotADirectory(path.to_path_buf()))
+    }
+
+    /// The voicebank root: a directory, or a folder inside an archive.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// The voicebank's display name from `character.yaml` or `character.txt`.
+    pub fn name(&self) -> Option<String> {
+        self.metadata("name")
+    }
+
+    /// A top-level value from `character.yaml` or, failing that, `character.txt`,
+    /// e.g. `author`.
+    pub fn metadata(&self, key: &str) -> Option<String> {
+        character::metadata(self, key)
+    }
+
+    /// Names the subbanks of the voicebank, e.g. the pitches or expressions of
+    /// a UTAU voicebank: the affixes declared in `character.yaml` or `prefix.map`,
+    /// and the folders with their own oto.ini. Sorted, without duplicates.
+    pub fn subbanks(&self) -> Result<Vec<String>> {
+        let mut subbanks = character::subbanks(self)?;
+        subbanks.extend(utau::subbanks(self)?);
+        subbanks.retain(|s| !s.is_empty());
+        subbanks.sort();
+        subbanks.dedup();
+        Ok(subbanks)
+    }
+
+    pub fn paths(&self) -> impl Iterator<Item = &str> {
+        self.paths.iter().map(String::as_str)
+    }
+
+    /// Finds a file referenced from inside the voicebank. References may use
+    /// backslashes and are matched case-insensitively, as they would be on Windows.
+    pub fn find(&self, reference: &str) -> Option<&str> {
+        let reference = text::normalize_path(reference).to_lowercase();
+        self.paths().find(|p| p.to_lowercase() == reference)
+    }
+
+    pub fn read(&self, relative: &str) -> Result<Vec<u8>> {
+        self.with_reader(relative, |reader| {
+            let mut contents = Vec::new();
+            reader.read_to_end(&mut contents)?;
+            Ok(contents)
+        })
+    }
+
+    /// Stamps a file for the digest cache. Only files in directories can be stamped.
+    pub(crate) fn stamp(&self, relative: &str) -> Option<FileStamp> {
+        match &self.source {
+            Source::Dir => FileStamp::of(&self.root.join(relative)),
+            #[cfg(feature = "archive")]
+            Source::Archive(_) => None,
+        }
+    }
+
+    /// Streams the contents of a file to `f`, so large files can be processed
+    /// with bounded memory.
+    pub(crate) fn with_reader<T>(&self, relative: &str, f: impl FnOnce(&mut dyn Read) -> io::Result<T>) -> Result<T> {
+        match &self.source {
+            Source::Dir => {
+                let path = self.root.join(relative);
+                let mut file = File::open(&path).map_err(|e| UsidError::io(&path, e))?;
+                f(&mut file).map_err(|e| UsidError::io(path, e))
+            }
+            #[cfg(feature = "archive")]
+            Source::Archive(archive) => archive.with_reader(relative, f),
+        }
+    }
+}
+
+fn walk(root: &Path, dir: &Path, rules: &IgnoreRules, paths: &mut Vec<String>) -> Result<()> {
+    let entries = fs::read_dir(dir).map_err(|e| UsidError::io(dir, e))?;
+
+    for entry in entries {
+        let entry = entry.map_err(|e| UsidError::io(dir, e))?;
+        let path = entry.path();
+        let file_type = entry.file_type().map_err(|e| UsidError::io(&path, e))?;
+
+        let relative = relative_path(root, &path);
+        if rules.is_ignored(&relative, file_type.is_dir()) {
+            continue;
+        }
+
+        if file_type.is_dir() {
+            walk(root, &path, rules, paths)?;
+        } else {
+            paths.push(relative);
+        }
+    }
+
+    Ok(())
+}
+
+/// Finds the voicebank root by descending through wrapper folders, i.e. folders
+/// that contain nothing but a single subfolder. Returns the root with a trailing
+/// `/`, or an empty string if the voicebank sits at the top of the listing.
+pub(crate) fn locate_root<'a>(names: impl Iterator<Item = &'a str> + Clone) -> String {
+    let mut root = String::new();
+
+    loop {
+        let mut children = names.clone().filter_map(|name| name.strip_prefix(root.as_str()));
+        let Some(first) = children.next() else {
+            return root;
+        };
+
+        let Some((folder, _)) = first.split_once('/') else {
+            return root;
+        };
+
+        let is_wrapper = children.all(|child| {
+            child.split_once('/').is_some_and(|(f, _)| f == folder)
+        });
+        if !is_wrapper {
+            return root;
+        }
+
+        root.push_str(folder);
+        root.push('/');
+    }
+}
+
+fn relative_path(root: &Path, path: &Path) -> String {
+    path.strip_prefix(root)
+        .unwrap_or(path)
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Lowercased file name of a relative path, for case-insensitive matching.
+pub(crate) fn file_name(relative: &str) -> String {
+    relative.rsplit('/').next().unwrap_or_default().to_lowercase()
+}
+
+/// Lowercased extension of a relative path, or an empty string.
+pub(crate) fn extension(relative: &str) -> String {
+    file_name(relative).rsplit_once('.').map(|(_, ext)| ext.to_string()).unwrap_or_default()
+}
+
+/// Reports whether a relative path is directly inside the voicebank root.
+pub(crate) fn is_root(relative: &str) -> bool {
+    !relative.contains('/')
+}
+
+pub(crate) fn has_image_extension(relative: &str) -> bool {
+    IMAGE_EXTENSIONS.contains(&extension(relative).as_str())
+}
+
+fn is_audio(relative: &str) -> bool {
+    extension(relative) == "wav"
+}
+
+/// Canonicalises an image to its pixels, or returns `None` if `relative` is not
+/// an image. Images that fail to decode are hashed byte for byte.
+pub(crate) fn canonicalize_image(relative: &str, contents: &[u8]) -> Option<Vec<u8>> {
+    if !has_image_extension(relative) {
+        return None;
+    }
+
+    Some(image::canonicalize(contents).unwrap_or_else(|| contents.to_vec()))
+}
+
+/// Computes the manifest of a voicebank, using the format from `options` that
+/// best matches it.
+pub(crate) fn manifest(
+    files: &VoicebankFiles,
+    options: &HashOptions,
+    algorithm: HashAlgorithm,
+) -> Result<UsidManifest> {
+    let Some(format) = options.formats.detect(files) else {
+        return Err(UsidError::NoIdentityFiles(files.root.clone()));
+    };
+
+    let mut identity = format.identity_files(files)?;
+    if identity.is_empty() {
+        return Err(UsidError::NoIdentityFiles(files.root.clone()));
+    }
+
+    if options.fingerprint_audio {
+        identity.extend(files.paths().filter(|p| is_audio(p)).map(String::from));
+    }
+
+    identity.sort();
+    identity.dedup();
+
+    // Entries are collected in the order of `identity`, so the result does not
+    // depend on how the work was scheduled
+    let hash_entries = || {
+        identity.into_par_iter()
+            .map(|relative| manifest_entry(files, format, options, algorithm, relative))
+            .collect::<Result<Vec<_>>>()
+    };
+
+    let mut entries = match &options.thread_pool {
+        Some(pool) => pool.install(hash_entries)?,
+        None => hash_entries()?,
+    };
+
+    let roles = character::image_roles(files)?;
+    for entry in &mut entries {
+        entry.role = roles.iter().find(|(relative, _)| *relative == entry.path).map(|(_, role)| role.clone());
+    }
+
+    Ok(UsidManifest {
+        usid: digest(&entries, algorithm).to_usid(),
+        format: format.name().to_string(),
+        algorithm,
+        algorithm_version: ALGORITHM_VERSION,
+        files: entries,
+        subbanks: files.subbanks()?,
+        family: lineage::family(files, format.name()),
+    })
+}
+
+fn manifest_entry(
+    files: &VoicebankFiles,
+    format: &dyn VoicebankFormat,
+    options: &HashOptions,
+    algorithm: HashAlgorithm,
+    relative: String,
+) -> Result<ManifestEntry> {
+    let fingerprint = options.fingerprint_audio && is_audio(&relative);
+    let normalization = if fingerprint {
+        Normalization::AudioEnvelope
+    } else {
+        format.normalization(&relative)
+    };
+
+    let Some(cache) = &options.cache else {
+        return hash_entry(files, format, algorithm, relative, normalization);
+    };
+
+    let stamp = files.stamp(&relative);
+    if let Some(entry) = stamp.as_ref().and_then(|s| cache.get(s, &normalization, algorithm, &relative)) {
+        return Ok(entry);
+    }
+
+    let entry = hash_entry(files, format, algorithm, relative, normalization)?;
+    if let Some(stamp) = stamp {
+        cache.insert(stamp, algorithm, &entry);
+    }
+    Ok(entry)
+}
+
+fn hash_entry(
+    files: &VoicebankFiles,
+    format: &dyn VoicebankFormat,
+    algorithm: HashAlgorithm,
+    relative: String,
+    normalization: Normalization,
+) -> Result<ManifestEntry> {
+    if normalization != Normalization::AudioEnvelope && format.streams(&relative) {
+        // Raw files such as models can be gigabytes, so stream them through the hasher
+        let (size, digest) = files.with_reader(&relative, |reader| {
+            let mut hasher = Hasher::new(algorithm);
+            let size = io::copy(reader, &mut hasher)?;
+            Ok((size, hasher.finalize_hex()))
+        })?;
+
+        return Ok(ManifestEntry {
+            path: relative,
+            size,
+            canonical_digest: digest.clone(),
+            digest,
+            normalization,
+            perceptual_hash: None,
+            role: None,
+        });
+    }
+
+    if normalization == Normalization::AudioEnvelope {
+        // Samples can be long recordings, so decode them while hashing their bytes
+        let (size, digest, envelope) = files.with_reader(&relative, |reader| {
+            let mut reader = HashingReader::new(reader, algorithm);
+            let envelope = audio::fingerprint(&mut reader);
+            io::copy(&mut reader, &mut io::sink())?;
+            let (size, digest) = reader.finish();
+            Ok((size, digest, envelope))
+        })?;
+
+        return Ok(ManifestEntry {
+            path: relative,
+            size,
+            // A sample that cannot be decoded is compared byte for byte
+            canonical_digest: envelope.as_deref().map(|e| algorithm.hex_digest(e)).unwrap_or_else(|| digest.clone()),
+            digest,
+            normalization,
+            perceptual_hash: None,
+            role: None,
+        });
+    }
+
+    let contents = files.read(&relative)?;
+    let size = contents.len() as u64;
+    let digest = algorithm.hex_digest(&contents);
+
+    let perceptual_hash = match normalization {
+        Normalization::Image => image::perceptual_hash(&contents).map(|h| format!("{:016x}", h)),
+        _ => None,
+    };
+
+    let canonical = format.canonicalize(&relative, contents);
+
+    Ok(ManifestEntry {
+        canonical_digest: algorithm.hex_digest(&canonical),
+        path: relative,
+        size,
+        digest,
+        normalization,
+        perceptual_hash,
+        role: None,
+    })
+}
+
+/// Folds the canonical digests of the identity files into a single digest.
+pub(crate) fn digest(entries: &[ManifestEntry], algorithm: HashAlgorithm) -> UsidDigest {
+    let mut hasher = Hasher::new(algorithm);
+    hasher.update(DOMAIN);
+
+    // Entries are sorted by path, but roles may sort differently
+    let mut entries = entries.iter().collect::<Vec<_>>();
+    entries.sort_by(|a, b| a.key().cmp(b.key()));
+
+    for entry in entries {
+        // Length-prefix every field so key/digest boundaries are unambiguous
+        hasher.update(&(entry.key().len() as u64).to_le_bytes());
+        hasher.update(entry.key().as_bytes());
+        hasher.update(&(entry.canonical_digest.len() as u64).to_le_bytes());
+        hasher.update(entry.canonical_digest.as_bytes());
+    }
+
+    UsidDigest::new(algorithm, hasher.finalize()).expect("hasher yields a full-length digest")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::USID;
+
+    fn write(root: &Path, files: &[&str]) {
+        for relative in files {
+            let path = root.join(relative);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, relative).unwrap();
+        }
+    }
+
+    #[test]
+    fn wrapper_folders_are_descended_into() {
+        let dir = tempfile::tempdir().unwrap();
+        write(dir.path(), &["Wrapper/Teto/character.txt", "Wrapper/Teto/A3/oto.ini"]);
+        let bank = dir.path().join("Wrapper/Teto");
+
+        let files = VoicebankFiles::from_dir(dir.path()).unwrap();
+        assert_eq!(files.root(), bank);
+        assert_eq!(files.paths().collect::<Vec<_>>(), ["A3/oto.ini", "character.txt"]);
+        assert_eq!(USID::from_voicebank(dir.path()).unwrap(), USID::from_voicebank(&bank).unwrap());
+    }
+
+    #[test]
+    fn folder_with_files_of_its_own_is_the_root() {
+        let dir = tempfile::tempdir().unwrap();
+        write(dir.path(), &["readme.txt", "A3/oto.ini"]);
+
+        let files = VoicebankFiles::from_dir(dir.path()).unwrap();
+        assert_eq!(files.root(), dir.path());
+        assert_eq!(files.paths().collect::<Vec<_>>(), ["A3/oto.ini", "readme.txt"]);
+    }
+}
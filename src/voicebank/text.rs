@@ -0,0 +1,80 @@
+use encoding_rs::SHIFT_JIS;
+
+const UTF8_BOM: &[u8] = b"\xef\xbb\xbf";
+
+/// Decodes a voicebank text file. UTF-8 (with or without BOM) is used when the
+/// bytes are valid UTF-8; anything else is read as Shift-JIS, which classic
+/// UTAU writes by default.
+pub(crate) fn decode(bytes: &[u8]) -> String {
+    let bytes = bytes.strip_prefix(UTF8_BOM).unwrap_or(bytes);
+    match std::str::from_utf8(bytes) {
+        Ok(s) => s.to_string(),
+        Err(_) => SHIFT_JIS.decode_without_bom_handling(bytes).0.into_owned(),
+    }
+}
+
+/// Iterates over the non-blank lines of `text` with surrounding whitespace
+/// removed. `\r\n`, `\n` and lone `\r` are all treated as line endings.
+pub(crate) fn lines(text: &str) -> impl Iterator<Item = &str> {
+    text.split(['\r', '\n'])
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+}
+
+/// Decodes `bytes` and joins its normalised lines with `\n`.
+pub(crate) fn normalize(bytes: &[u8]) -> String {
+    lines(&decode(bytes)).collect::<Vec<_>>().join("\n")
+}
+
+/// Like [`normalize`], but also sorts the lines, for files whose line order
+/// carries no meaning.
+pub(crate) fn normalize_unordered(bytes: &[u8]) -> String {
+    let text = decode(bytes);
+    let mut lines = lines(&text).collect::<Vec<_>>();
+    lines.sort_unstable();
+    lines.join("\n")
+}
+
+/// Formats a numeric field in its shortest form, so `100`, `100.0` and
+/// ` 100.000 ` all canonicalise to `100`. Non-numeric fields are only trimmed.
+pub(crate) fn normalize_number(field: &str) -> String {
+    let field = field.trim();
+    match field.parse::<f64>() {
+        Ok(value) if value.is_finite() => {
+            // Avoid rendering negative zero as "-0"
+            let value = if value == 0.0 { 0.0 } else { value };
+            value.to_string()
+        }
+        _ => field.to_string(),
+    }
+}
+
+/// Normalises a path referenced from inside a voicebank file. Voicebanks are
+/// mostly authored on Windows, so references may use backslashes.
+pub(crate) fn normalize_path(path: &str) -> String {
+    let path = path.trim().replace('\\', "/");
+    path.strip_prefix("./").unwrap_or(&path).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_ignores_bom_line_endings_and_blank_lines() {
+        assert_eq!(normalize(b"\xef\xbb\xbfa \r\n\r\n b\rc\n"), "a\nb\nc");
+    }
+
+    #[test]
+    fn normalize_unordered_sorts_lines() {
+        assert_eq!(normalize_unordered(b"b\na\n"), "a\nb");
+    }
+
+    #[test]
+    fn normalize_number_uses_shortest_form() {
+        assert_eq!(normalize_number(" 100.000 "), "100");
+        assert_eq!(normalize_number("-0"), "0");
+        assert_eq!(normalize_number("1.50"), "1.5");
+        assert_eq!(normalize_number(" abc "), "abc");
+    }
+}
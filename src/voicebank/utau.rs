@@ -0,0 +1,145 @@
+use crate::error::Result;
+use crate::manifest::Normalization;
+
+use super::{character, file_name, is_root, text, VoicebankFiles, VoicebankFormat};
+
+/// Number of numeric fields following the alias in an oto.ini entry:
+/// offset, consonant, cutoff, preutterance and overlap.
+const OTO_NUMERIC_FIELDS: usize = 5;
+
+/// Classic UTAU and OpenUtau voicebanks. Identity is defined by the character
+/// metadata, `prefix.map` and every `oto.ini`.
+pub struct UtauFormat;
+
+impl VoicebankFormat for UtauFormat {
+    fn name(&self) -> &str {
+        "utau"
+    }
+
+    fn detect(&self, files: &VoicebankFiles) -> f32 {
+        let is_utau = files.paths().any(|p| {
+            let name = file_name(p);
+            (is_root(p) && (name == "character.txt" || name == "character.yaml")) || name == "oto.ini"
+        });
+
+        if is_utau { 0.5 } else { 0.0 }
+    }
+
+    fn identity_files(&self, files: &VoicebankFiles) -> Result<Vec<String>> {
+        let mut identity = character::identity_files(files)?;
+        identity.extend(
+            files.paths()
+                .filter(|p| file_name(p) == "oto.ini" || (is_root(p) && file_name(p) == "prefix.map"))
+                .map(String::from),
+        );
+        Ok(identity)
+    }
+
+    fn canonicalize(&self, relative: &str, contents: Vec<u8>) -> Vec<u8> {
+        if let Some(canonical) = character::canonicalize(relative, &contents) {
+            return canonical;
+        }
+
+        match file_name(relative).as_str() {
+            "oto.ini" => canonical_oto(&contents).into_bytes(),
+            "prefix.map" => text::normalize_unordered(&contents).into_bytes(),
+            _ => contents,
+        }
+    }
+
+    fn normalization(&self, relative: &str) -> Normalization {
+        if let Some(normalization) = character::normalization(relative) {
+            return normalization;
+        }
+
+        match file_name(relative).as_str() {
+            "oto.ini" => Normalization::Oto,
+            "prefix.map" => Normalization::SortedLines,
+            _ => Normalization::None,
+        }
+    }
+
+    fn streams(&self, relative: &str) -> bool {
+        self.normalization(relative) == Normalization::None
+    }
+}
+
+/// Lists the subbanks of a UTAU voicebank: the affixes `prefix.map` maps notes
+/// to, and every folder below the root with its own oto.ini.
+pub(crate) fn subbanks(files: &VoicebankFiles) -> Result<Vec<String>> {
+    let mut subbanks = Vec::new();
+
+    if let Some(prefix_map) = files.paths().find(|p| is_root(p) && file_name(p) == "prefix.map") {
+        let text = text::decode(&files.read(prefix_map)?);
+        for line in text::lines(&text) {
+            // Each line maps a note to a prefix and a suffix, separated by tabs
+            let mut fields = line.split('\t').skip(1);
+            let prefix = fields.next().unwrap_or_default();
+            let suffix = fields.next().unwrap_or_default();
+            subbanks.push(format!("{}{}", prefix, suffix));
+        }
+    }
+
+    subbanks.extend(
+        files.paths()
+            .filter(|p| !is_root(p) && file_name(p) == "oto.ini")
+            .filter_map(|p| p.rsplit_once('/'))
+            .map(|(folder, _)| folder.to_string()),
+    );
+
+    Ok(subbanks)
+}
+
+/// Normalises an oto.ini so that the ID does not depend on the editor that saved
+/// it: entries are sorted, numbers are written in their shortest form, missing
+/// numeric fields default to zero and an empty alias falls back to the sample name.
+fn canonical_oto(bytes: &[u8]) -> String {
+    let text = text::decode(bytes);
+    let mut entries = text::lines(&text)
+        .map(|line| {
+            let Some((wav, params)) = line.split_once('=') else {
+                return line.to_string();
+            };
+
+            let wav = text::normalize_path(wav);
+            let mut fields = params.split(',');
+            let alias = match fields.next().map(str::trim) {
+                Some(alias) if !alias.is_empty() => alias.to_string(),
+                _ => wav.rsplit_once('.').map_or(wav.as_str(), |(stem, _)| stem).to_string(),
+            };
+
+            let mut numbers = fields.map(text::normalize_number).collect::<Vec<_>>();
+            if numbers.len() < OTO_NUMERIC_FIELDS {
+                numbers.resize(OTO_NUMERIC_FIELDS, "0".to_string());
+            }
+
+            format!("{}={},{}", wav, alias, numbers.join(","))
+        })
+        .collect::<Vec<_>>();
+
+    entries.sort_unstable();
+    entries.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn oto_ignores_order_line_endings_and_number_formatting() {
+        let a = canonical_oto(b"a.wav=a,100,50,-20,30,10\nka.wav=ka,0,0,0,0,0\n");
+        let b = canonical_oto(b"ka.wav=ka,0.0,0,0,0,0\r\n a.wav = a , 100.000,50,-20,30,10\r\n");
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn oto_fills_in_missing_alias_and_fields() {
+        assert_eq!(canonical_oto(b"a.wav=,100"), "a.wav=a,100,0,0,0,0");
+    }
+
+    #[test]
+    fn oto_decodes_shift_jis() {
+        let (shift_jis, _, _) = encoding_rs::SHIFT_JIS.encode("あ.wav=あ,0,0,0,0,0");
+        assert_eq!(canonical_oto(&shift_jis), canonical_oto("あ.wav=あ,0,0,0,0,0".as_bytes()));
+    }
+}
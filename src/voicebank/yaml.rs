@@ -0,0 +1,129 @@
+use yaml_rust2::{Yaml, YamlLoader};
+
+use super::text;
+
+/// Canonicalises a YAML document: mappings are sorted by key, numbers are written
+/// in their shortest form and comments, quoting style and indentation are dropped.
+/// Documents that fail to parse fall back to plain text normalisation.
+pub(crate) fn canonical(bytes: &[u8]) -> String {
+    canonical_without(bytes, &[])
+}
+
+/// Like [`canonical`], leaving out the given top-level keys.
+pub(crate) fn canonical_without(bytes: &[u8], keys: &[&str]) -> String {
+    match parse(bytes) {
+        Some(mut value) => {
+            if let Yaml::Hash(mapping) = &mut value {
+                mapping.retain(|key, _| !key.as_str().is_some_and(|k| keys.contains(&k)));
+            }
+
+            let mut out = String::new();
+            write_value(&value, &mut out);
+            out
+        }
+        None => text::normalize(bytes),
+    }
+}
+
+/// Parses a single YAML document. An empty file is `null`; several documents in
+/// one file are treated as a parse failure.
+pub(crate) fn parse(bytes: &[u8]) -> Option<Yaml> {
+    let mut documents = YamlLoader::load_from_str(&text::decode(bytes)).ok()?;
+    match documents.len() {
+        0 => Some(Yaml::Null),
+        1 => documents.pop(),
+        _ => None,
+    }
+}
+
+/// Looks up `key` in a mapping.
+pub(crate) fn get<'a>(value: &'a Yaml, key: &str) -> Option<&'a Yaml> {
+    value.as_hash()?.get(&Yaml::String(key.to_string()))
+}
+
+/// Returns the string values of the given top-level keys.
+pub(crate) fn top_level_strings(value: &Yaml, keys: &[&str]) -> Vec<String> {
+    keys.iter()
+        .filter_map(|key| get(value, key)?.as_str())
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+fn write_value(value: &Yaml, out: &mut String) {
+    match value {
+        // Aliases are resolved while loading, so are never left in a document
+        Yaml::Null | Yaml::Alias(_) | Yaml::BadValue => out.push_str("null"),
+        Yaml::Boolean(b) => out.push_str(&b.to_string()),
+        Yaml::Integer(n) => out.push_str(&n.to_string()),
+        Yaml::Real(n) => out.push_str(&text::normalize_number(n)),
+        Yaml::String(s) => out.push_str(&format!("{:?}", s)),
+        Yaml::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_value(item, out);
+            }
+            out.push(']');
+        }
+        Yaml::Hash(mapping) => {
+            let mut entries = mapping.iter()
+                .map(|(key, value)| {
+                    let mut key_out = String::new();
+                    write_value(key, &mut key_out);
+                    (key_out, value)
+                })
+                .collect::<Vec<_>>();
+            entries.sort_by(|a, b| a.0.cmp(&b.0));
+
+            out.push('{');
+            for (i, (key, value)) in entries.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                out.push_str(&key);
+                out.push(':');
+                write_value(value, out);
+            }
+            out.push('}');
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn canonical_ignores_key_order_comments_and_style() {
+        let a = canonical(b"name: Teto\nsample_rate: 44100\nscale: 1.50\nlist: [a, 'b']\n");
+        let b = canonical(b"# Teto\nlist:\n  - \"a\"\n  - b\nscale:   1.5\nsample_rate: 44100\nname: 'Teto'\n");
+        assert_eq!(a, b);
+        assert_eq!(a, r#"{"list":["a","b"],"name":"Teto","sample_rate":44100,"scale":1.5}"#);
+    }
+
+    #[test]
+    fn canonical_sorts_nested_mappings() {
+        assert_eq!(canonical(b"b: {d: 2, c: ~}\na: true\n"), r#"{"a":true,"b":{"c":null,"d":2}}"#);
+    }
+
+    #[test]
+    fn canonical_without_drops_top_level_keys_only() {
+        let canonical = canonical_without(b"name: Teto\nportrait: a.png\nnested: {portrait: b.png}\n", &["portrait"]);
+        assert_eq!(canonical, r#"{"name":"Teto","nested":{"portrait":"b.png"}}"#);
+    }
+
+    #[test]
+    fn invalid_documents_fall_back_to_text() {
+        assert_eq!(canonical(b"not: [valid\r\n"), "not: [valid");
+        assert_eq!(canonical(b"a: 1\n---\nb: 2\n"), "a: 1\n---\nb: 2");
+    }
+
+    #[test]
+    fn top_level_strings_skips_missing_and_empty_values() {
+        let value = parse(b"name: ' Teto '\nicon: ''\nsize: 3\n").unwrap();
+        assert_eq!(top_level_strings(&value, &["name", "icon", "size", "missing"]), ["Teto"]);
+    }
+}